@@ -0,0 +1,164 @@
+use serde::Deserialize;
+use std::env;
+
+use super::download;
+
+const DEFAULT_URL: &str = "https://godotengine.org/asset-library/api";
+
+#[derive(Deserialize)]
+pub struct Asset {
+    pub asset_id: String,
+    pub title: String,
+    /// Edit count of the asset, increased on every new release
+    pub version: String,
+    #[serde(default)]
+    pub version_string: String,
+    #[serde(default)]
+    pub download_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct AssetSummary {
+    pub asset_id: String,
+    pub title: String,
+}
+
+#[derive(Deserialize)]
+struct SearchResult {
+    result: Vec<AssetSummary>,
+}
+
+// API of the asset library: $GLAM_ASSET_LIBRARY_URL, or the official one
+fn base_url() -> String {
+    let url = env::var("GLAM_ASSET_LIBRARY_URL").unwrap_or_else(|_| DEFAULT_URL.to_string());
+    return url.trim_end_matches('/').to_string();
+}
+
+pub fn get_asset(id: &str) -> Result<Asset, String> {
+    let url = format!("{}/asset/{}", base_url(), id);
+    let value = download::get_json(&url, &[])?;
+    return serde_json::from_value(value).map_err(|e| format!("invalid asset {}: {}", id, e));
+}
+
+pub fn search_assets(query: &str) -> Result<Vec<AssetSummary>, String> {
+    let url = format!("{}/asset", base_url());
+    let value = download::get_json(&url, &[("filter", query), ("type", "addon")])?;
+    let search: SearchResult = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    return Ok(search.result);
+}
+
+// Package name for an asset title, like "dialogue-manager" for "Dialogue Manager"
+pub fn package_name(title: &str) -> String {
+    let name = title
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    return name
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    // Stand-in for the asset library, answering each request with the JSON of its path
+    // and sending the request paths back
+    fn serve(responses: Vec<(&'static str, &'static str)>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(responses.len()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+                let (status, body) = responses
+                    .iter()
+                    .find(|(prefix, _)| path.starts_with(prefix))
+                    .map(|(_, body)| ("200 OK", *body))
+                    .unwrap_or(("404 Not Found", "{\"error\": \"not found\"}"));
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                sender.send(path).unwrap();
+            }
+        });
+
+        return (url, receiver);
+    }
+
+    #[test]
+    fn asset_library_api() {
+        let (url, requests) = serve(vec![
+            (
+                "/api/asset/1234",
+                "{\"asset_id\": \"1234\", \"title\": \"Dialogue Manager\", \"version\": \"12\", \
+                 \"version_string\": \"2.1.0\", \"download_url\": \"http://example.com/addon.zip\"}",
+            ),
+            (
+                "/api/asset?",
+                "{\"result\": [{\"asset_id\": \"1234\", \"title\": \"Dialogue Manager\"}]}",
+            ),
+            ("/api/asset/missing", "{}"),
+        ]);
+        env::set_var("GLAM_ASSET_LIBRARY_URL", format!("{}/", url));
+
+        let asset = get_asset("1234").unwrap();
+        assert_eq!(requests.recv().unwrap(), "/api/asset/1234");
+        assert_eq!(asset.title, "Dialogue Manager");
+        assert_eq!(asset.version, "12");
+        assert_eq!(asset.version_string, "2.1.0");
+        assert_eq!(asset.download_url, "http://example.com/addon.zip");
+
+        let results = search_assets("dialogue manager").unwrap();
+        assert_eq!(
+            requests.recv().unwrap(),
+            "/api/asset?filter=dialogue+manager&type=addon"
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].asset_id, "1234");
+
+        assert!(get_asset("missing").is_err());
+        assert_eq!(requests.recv().unwrap(), "/api/asset/missing");
+    }
+
+    #[test]
+    fn package_names() {
+        assert_eq!(package_name("Dialogue Manager"), "dialogue-manager");
+        assert_eq!(
+            package_name("  Godot-Steam (GDExtension) "),
+            "godot-steam-gdextension"
+        );
+        assert_eq!(package_name("snake_case_addon"), "snake_case_addon");
+    }
+}
@@ -0,0 +1,30 @@
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::PathBuf;
+
+use super::utils;
+
+// User-level cache shared by every project: $GLAM_CACHE_DIR, or glam/ inside
+// $XDG_CACHE_HOME (~/.cache by default)
+pub fn cache_folder() -> Option<PathBuf> {
+    if let Some(folder) = env::var_os("GLAM_CACHE_DIR") {
+        return Some(PathBuf::from(folder));
+    }
+    if let Some(folder) = env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(folder).join("glam"));
+    }
+    let home = env::var_os("HOME")?;
+    return Some(PathBuf::from(home).join(".cache").join("glam"));
+}
+
+// Bare mirror of a repository, keyed by its URL
+pub fn mirror_path(url: &str) -> Option<PathBuf> {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let name = format!("{}-{}.git", utils::get_repo_name(url), &hash[..12]);
+    return Some(cache_folder()?.join("mirrors").join(name));
+}
+
+// Downloaded archive, keyed by its sha256
+pub fn archive_path(sha256: &str) -> Option<PathBuf> {
+    return Some(cache_folder()?.join("archives").join(sha256));
+}
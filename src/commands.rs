@@ -1,14 +1,41 @@
-use inquire::{Select, Text, MultiSelect};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::fs::write;
 use std::path::{Path, PathBuf};
 use std::process::exit;
-
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[path = "asset_library.rs"]
+mod asset_library;
+#[path = "cache.rs"]
+mod cache;
 #[path = "content.rs"]
 mod content;
+#[path = "diff.rs"]
+mod diff;
+#[path = "config_file.rs"]
+mod config_file;
+#[path = "download.rs"]
+mod download;
+#[path = "files.rs"]
+mod files;
+#[path = "git.rs"]
+mod git;
+#[path = "release.rs"]
+mod release;
+#[path = "prompt.rs"]
+mod prompt;
 #[path = "utils.rs"]
 mod utils;
+#[path = "version.rs"]
+mod version;
+
+pub use utils::log_to_stderr;
 
 #[derive(Serialize, Deserialize)]
 struct GlamObject {
@@ -18,11 +45,72 @@ struct GlamObject {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct GlamPackage {
     name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     git_repo: String,
     #[serde(default = "default_string")]
     commit: String,
+    /// Branch to follow instead of the remote default branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    /// Tag to install
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    /// Semver requirement (e.g. "^2.1") resolved against the repository tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
     #[serde(default)]
     links: Vec<Link>,
+    /// Godot Asset Library id, for packages downloaded from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asset_id: Option<String>,
+    /// Asset Library version (edit count) of the archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asset_version: Option<String>,
+    /// Local folder with the package, used instead of a .glam.d repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// Whether addon folders are copies of the source folders or symlinks to them
+    #[serde(default, skip_serializing_if = "InstallMode::is_copy")]
+    mode: InstallMode,
+    /// Forge release the archive comes from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    release: Option<Release>,
+    /// Archive the package is extracted from, instead of cloning a repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archive: Option<Archive>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum InstallMode {
+    #[default]
+    Copy,
+    Symlink,
+}
+
+impl InstallMode {
+    fn is_copy(&self) -> bool {
+        return *self == InstallMode::Copy;
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Release {
+    /// Name of the release asset, with `*` wildcards
+    asset: String,
+    /// "github" or "gitlab"
+    forge: String,
+    /// API of the forge, when it isn't the public one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Archive {
+    url: String,
+    /// Checksum of the archive, filled on its first download
+    #[serde(default)]
+    sha256: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -31,6 +119,86 @@ struct Link {
     source_folder: String,
 }
 
+/// Contents of the .glam.lock file, what was actually installed for each package
+#[derive(Default, Serialize, Deserialize)]
+struct GlamLock {
+    packages: Vec<LockedPackage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LockedPackage {
+    name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    git_repo: String,
+    /// Resolved commit hash
+    commit: String,
+    /// Tag the commit was resolved from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    /// Hash of all installed files
+    content_hash: String,
+    links: Vec<LockedLink>,
+    /// Packages this one depends on, declared on its own .glam file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dependencies: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LockedLink {
+    target_folder: String,
+    /// Files installed on the target folder, and their hashes
+    files: BTreeMap<String, String>,
+}
+
+
+/// Package settings given as command flags instead of prompted
+pub struct PackageOptions {
+    pub name: Option<String>,
+    pub commit: Option<String>,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub version: Option<String>,
+    /// Addon folders to install, when the repository has several
+    pub links: Vec<String>,
+    /// Godot Asset Library id or name, instead of a git repository
+    pub asset: Option<String>,
+    /// Local folder with the package, instead of a git repository
+    pub path: Option<String>,
+    /// Symlink addon folders to the package instead of copying them
+    pub link: bool,
+    /// Release asset name pattern, to download it instead of cloning the repository
+    pub release: Option<String>,
+    pub forge: Option<String>,
+    pub api_url: Option<String>,
+}
+
+pub use release::FORGES;
+
+/// Flags of glam update
+pub struct UpdateOptions {
+    /// Update every package instead of the given ones
+    pub all: bool,
+    /// Allow crossing the major release of version requirements
+    pub major: bool,
+    /// Only show what would change
+    pub dry_run: bool,
+    /// What to do with addon files changed locally
+    pub local_changes: Option<String>,
+}
+
+/// Flags of glam apply
+pub struct ApplyOptions {
+    pub package: Option<String>,
+    /// Only show what would change
+    pub dry_run: bool,
+    /// Commit the applied changes on the package repository
+    pub commit: bool,
+    pub message: Option<String>,
+    /// Push the commit to origin
+    pub push: bool,
+    /// New branch to commit to
+    pub branch: Option<String>,
+}
 
 fn default_string() -> String {
     return "".to_string();
@@ -79,7 +247,7 @@ pub fn initialize(root: &str) {
 pub fn initialize_glam_files(root: &str) {
     // Create glam.d/ folder if it doesn't exist
     if !Path::new(&format!("{}/.glam.d/", root)).exists() {
-        let res = utils::run_shell_command("mkdir -p .glam.d", &root, false);
+        let res = fs::create_dir_all(format!("{}/.glam.d", root));
 
         utils::assert_result(&res, "Couldn't create .glam.d/ folder!");
 
@@ -99,7 +267,7 @@ pub fn initialize_glam_files(root: &str) {
 
     // Create .glam file if it doesn't exist
     if !Path::new(&format!("{}/.glam", root)).exists() {
-        fs::write(&format!("{}/.glam", root), content::create_glam_file())
+        fs::write(format!("{}/.glam", root), content::create_glam_file())
             .expect("Couldn't create .glam file!");
         utils::log_info("Created .glam file");
     }
@@ -126,444 +294,2538 @@ pub fn check_initialization(root: &str) -> bool {
     return ret;
 }
 
-pub fn install_repositories(root: &str, verbose: bool) {
+pub fn install_repositories(
+    root: &str,
+    jobs: usize,
+    offline: bool,
+    local_changes: Option<&str>,
+    interactive: bool,
+    verbose: bool,
+) {
     let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
     let mut glam_object = read_glam_file(&glam_file_path);
+    let mut glam_lock = read_lock_file(&glam_lock_path);
     let mut glam_packages = glam_object.packages;
 
+    let top_level = glam_packages.len();
+
+    fetch_packages(root, &mut glam_packages, jobs, offline);
+    if offline {
+        check_locked_commits(root, &glam_packages, &glam_lock);
+    }
+    let graph = resolve_dependencies(root, &mut glam_packages, &glam_lock, &mut vec![], jobs, offline);
+
+    for package in glam_packages.iter_mut() {
+        select_package_links(root, package, &[], interactive);
+    }
+    check_link_conflicts(&glam_packages);
+
+    let (action, applied) = protect_local_changes(
+        root,
+        glam_packages.iter().collect(),
+        &mut glam_lock,
+        false,
+        local_changes,
+        interactive,
+        verbose,
+    );
+
     for package in glam_packages.iter_mut() {
+        if applied.contains(&package.name) {
+            continue;
+        }
+
         utils::log_info(&format!("Installing {}...", package.name));
-        clone_or_fetch_package(root, package, verbose);
-        let commit = package.commit.to_string();
-        install_glam_package(root, &commit, package, false, true, verbose);
+        let locked = find_locked_package(&glam_lock, &package.name);
+        let merge = action == "merge";
+        let locked = install_glam_package(root, package, locked, false, merge, interactive, verbose);
+        lock_package(&mut glam_lock, locked);
     }
 
+    // Dependencies are only recorded on the lock
+    glam_packages.truncate(top_level);
+    link_dependencies(&mut glam_lock, &graph);
+    prune_dependencies(root, &mut glam_lock, &glam_packages);
+
     glam_object.packages = glam_packages;
     write_glam_file(&glam_file_path, &glam_object);
+    write_lock_file(&glam_lock_path, &glam_lock);
 }
 
-pub fn add_repository(root: &str, git_repo: &str, verbose: bool) {
+pub fn add_repository(root: &str, git_repo: &str, options: &PackageOptions, interactive: bool, verbose: bool) {
     let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
     let mut glam_object = read_glam_file(&glam_file_path);
+    let mut glam_lock = read_lock_file(&glam_lock_path);
     let mut glam_packages = glam_object.packages;
 
-    if find_package_by_repository(&glam_packages, &git_repo).is_some() {
+    let asset = options.asset.as_deref().map(|query| find_asset(query, interactive));
+
+    if let Some(asset) = &asset {
+        if glam_packages.iter().any(|p| p.asset_id.as_ref() == Some(&asset.asset_id)) {
+            utils::log_error("Asset already exists!");
+            exit(1);
+        }
+    } else if let Some(path) = &options.path {
+        if glam_packages.iter().any(|p| p.path.as_ref() == Some(path)) {
+            utils::log_error("Path already exists!");
+            exit(1);
+        }
+    } else if find_package_by_repository(&glam_packages, git_repo).is_some() {
         utils::log_error("Repository already exists!");
         exit(1);
     }
 
-    let default_name: String = utils::get_repo_name(git_repo);
+    let default_name: String = match (&asset, &options.path) {
+        (Some(asset), _) => asset_library::package_name(&asset.title),
+        (None, Some(path)) => utils::get_repo_name(path.trim_end_matches('/')),
+        (None, None) => utils::get_repo_name(git_repo),
+    };
     let inquire_name = "Name of the addon:";
-    let name = Text::new(inquire_name)
-        .with_default(&default_name)
-        .with_placeholder(&default_name)
-        .prompt()
-        .unwrap();
+    let name = prompt::text(inquire_name, &default_name, options.name.as_deref(), interactive);
 
     if find_package_by_name(&glam_packages, &name).is_some() {
         utils::log_error("Addon name exists!");
         exit(1);
     }
 
+    let release = options.release.as_ref().map(|pattern| Release {
+        asset: pattern.to_string(),
+        forge: options.forge.clone().unwrap_or_else(|| release::guess_forge(git_repo)),
+        api_url: options.api_url.clone(),
+    });
+    let release_asset = release.as_ref().map(|release| {
+        let res = find_release_asset(git_repo, release, options.tag.as_deref());
+        utils::assert_result(&res, "Couldn't find the release asset!");
+        let asset = res.unwrap();
+        utils::log_info(&format!("Using {} from release {}", asset.name, asset.tag));
+        asset
+    });
+
+    // Branches, tags, versions, assets, releases and paths already say which commit to use
     let default_commit = "latest";
-    let inquire_commit = "Commit hash of the repository:";
-    let commit = Text::new(inquire_commit)
-        .with_default(&default_commit)
-        .with_placeholder(&default_commit)
-        .prompt()
-        .unwrap();
+    let commit = if options.branch.is_some()
+        || options.tag.is_some()
+        || options.version.is_some()
+        || asset.is_some()
+        || release.is_some()
+        || options.path.is_some()
+    {
+        default_commit.to_string()
+    } else {
+        let inquire_commit = "Commit hash of the repository:";
+        prompt::text(inquire_commit, default_commit, options.commit.as_deref(), interactive)
+    };
 
     glam_packages.push(GlamPackage {
         name: name.to_string(),
         git_repo: git_repo.to_string(),
         commit: commit.to_string(),
+        branch: options.branch.clone(),
+        tag: options.tag.clone(),
+        version: options.version.clone(),
         links: [].to_vec(),
+        asset_id: asset.as_ref().map(|a| a.asset_id.to_string()),
+        asset_version: asset.as_ref().map(|a| a.version.to_string()),
+        path: options.path.clone(),
+        mode: if options.link { InstallMode::Symlink } else { InstallMode::Copy },
+        release,
+        archive: asset
+            .as_ref()
+            .map(|a| a.download_url.to_string())
+            .or(release_asset.map(|a| a.url))
+            .map(|url| Archive {
+                url,
+                sha256: "".to_string(),
+            }),
     });
 
+    let top_level = glam_packages.len();
     let target_package = glam_packages.last_mut().unwrap();
 
-    clone_or_fetch_package(root, target_package, verbose);
-    install_glam_package(root, &commit, target_package, false, true, verbose);
+    clone_or_fetch_package(root, target_package, true);
+    select_package_links(root, target_package, &options.links, interactive);
+
+    // The new package and the dependencies it brings get installed
+    let mut added = vec![name.to_string()];
+    let graph = resolve_dependencies(root, &mut glam_packages, &glam_lock, &mut added, DEPENDENCY_JOBS, false);
+    for package in glam_packages.iter_mut().skip(top_level - 1) {
+        select_package_links(root, package, &[], interactive);
+    }
+    check_link_conflicts(&glam_packages);
+
+    for package in glam_packages.iter_mut() {
+        if !added.contains(&package.name) && find_locked_package(&glam_lock, &package.name).is_some() {
+            continue;
+        }
+
+        let locked = install_glam_package(root, package, None, false, false, interactive, verbose);
+        lock_package(&mut glam_lock, locked);
+    }
+
+    glam_packages.truncate(top_level);
+    link_dependencies(&mut glam_lock, &graph);
+    prune_dependencies(root, &mut glam_lock, &glam_packages);
 
     glam_object.packages = glam_packages;
     write_glam_file(&glam_file_path, &glam_object);
+    write_lock_file(&glam_lock_path, &glam_lock);
 }
 
-pub fn create_addon(root: &str, verbose: bool) {
+pub fn create_addon(
+    root: &str,
+    addon: Option<&str>,
+    name: Option<&str>,
+    path: Option<&str>,
+    link: bool,
+    interactive: bool,
+    verbose: bool,
+) {
     let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
     let mut glam_object = read_glam_file(&glam_file_path);
+    let mut glam_lock = read_lock_file(&glam_lock_path);
     let mut glam_packages = glam_object.packages;
 
-    let folders = list_addons(root, verbose);
+    let folders = list_addons(root);
     
-    let addon_name = Select::new("Which addon you'll create a repository?", folders)
-        .prompt()
-        .unwrap();
+    let addon_name = prompt::select(
+        "Which addon you'll create a repository?",
+        folders,
+        addon,
+        "--addon",
+        interactive,
+    );
 
     if find_package_by_link(&glam_packages, &addon_name).is_some() {
         utils::log_error("There is a repository linked to that addon already!");
         exit(1);
     }
 
-    let repo_name = Text::new("Name of the repository:")
-        .with_default(&addon_name)
-        .with_placeholder(&addon_name)
-        .prompt()
-        .unwrap();
-    
-    let res = utils::run_shell_command(
-        &format!("mkdir -p .glam.d/{}/addons/{}", repo_name, addon_name),
-        &root,
-        verbose
-    );
-
-    utils::assert_result(&res, "Repository folder failed to be created!");
-
-    let res = utils::run_shell_command(
-        &format!("cd .glam.d/{} && git init", repo_name),
-        &root,
-        verbose,
-    );
+    let repo_name = prompt::text("Name of the repository:", &addon_name, name, interactive);
 
-    utils::assert_result(&res, "Repository failed to be initialized!");
-    
-    glam_packages.push(GlamPackage {
+    let package = GlamPackage {
         name: repo_name.to_string(),
         git_repo: "".to_string(),
         commit: "".to_string(),
+        branch: None,
+        tag: None,
+        version: None,
         links: [Link{
             target_folder: format!("addons/{}", addon_name),
             source_folder: format!("addons/{}", addon_name),
         }].to_vec(),
-    });    
+        asset_id: None,
+        asset_version: None,
+        path: path.map(|p| p.trim_end_matches('/').to_string()),
+        mode: if link { InstallMode::Symlink } else { InstallMode::Copy },
+        release: None,
+        archive: None,
+    };
+    let package_path = package_dir(root, &package);
+    // An existing local folder is already a working copy, with its own history
+    let existing = package.path.is_some() && Path::new(&package_path).exists();
+
+    let res = fs::create_dir_all(format!("{}/addons/{}", package_path, addon_name));
+
+    utils::assert_result(&res, "Repository folder failed to be created!");
+
+    if !existing {
+        let res = git::backend().init(Path::new(&package_path));
+
+        utils::assert_result(&res, "Repository failed to be initialized!");
+    }
+
+    glam_packages.push(package);
     
     glam_object.packages = glam_packages;
     write_glam_file(&glam_file_path, &glam_object);
 
-    let target_package = glam_object.packages.last_mut().unwrap();
+    let target_package = glam_object.packages.last().unwrap();
     
-    apply_package_files(&root, &target_package, verbose);
+    apply_package_files(root, target_package, &mut glam_lock, verbose);
+    if link {
+        let locked = find_locked_package(&glam_lock, &target_package.name);
+        let locked_link = locked.and_then(|l| find_locked_link(l, &target_package.links[0].target_folder));
+        link_addon_folder(root, target_package, &target_package.links[0], locked_link);
+    }
+    write_lock_file(&glam_lock_path, &glam_lock);
 }
 
-pub fn update_repository(root: &str, verbose: bool) {
+pub fn update_repository(
+    root: &str,
+    names: &[String],
+    options: &UpdateOptions,
+    interactive: bool,
+    verbose: bool,
+) {
     let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
     let mut glam_object = read_glam_file(&glam_file_path);
+    let mut glam_lock = read_lock_file(&glam_lock_path);
     let mut glam_packages = glam_object.packages;
 
-    if glam_packages.is_empty() {
-        utils::log_error("No addons to update!")
-    }
-
-    let options = glam_packages
+    let package_names = glam_packages
         .iter()
-        .map(|x| -> &str { &x.name })
-        .collect::<Vec<&str>>();
+        .map(|x| x.name.to_string())
+        .collect::<Vec<String>>();
 
-    if options.len() == 0 {
+    if package_names.is_empty() {
         utils::log_error("No repository to update!");
         exit(1);
     }
 
-    let ans = Select::new("Which addon you want to update?", options)
-        .prompt()
-        .unwrap();
+    let selected = if options.all {
+        package_names
+    } else {
+        prompt::multi_select(
+            "Which addons you want to update?",
+            package_names,
+            names,
+            "package names or --all",
+            interactive,
+        )
+    };
+
+    if selected.is_empty() {
+        utils::log_error("No addon selected!");
+        exit(1);
+    }
+
+    for name in &selected {
+        let package_index = find_package_by_name(&glam_packages, name).unwrap();
+        let target_package = &mut glam_packages[package_index];
 
-    let package_index = find_package_by_name(&glam_packages, ans).unwrap();
-    let target_package = &mut glam_packages[package_index];
+        utils::log_info(&format!("Fetching {}...", target_package.name));
+        if target_package.asset_id.is_some() {
+            refresh_asset(target_package);
+        } else if target_package.release.is_some() {
+            refresh_release(target_package);
+        }
+        // A dry run only fetches, the checkout stays on the installed commit
+        clone_or_fetch_package(root, target_package, !options.dry_run);
+        if options.major {
+            bump_version_requirement(root, target_package);
+        }
+    }
+
+    if options.dry_run {
+        for name in &selected {
+            let package = &glam_packages[find_package_by_name(&glam_packages, name).unwrap()];
+            let previous = find_locked_package(&glam_lock, &package.name);
+            let old_commit = previous.map(|l| l.commit.to_string()).unwrap_or_default();
+            let new_commit = fetched_commit(root, package, previous);
+
+            utils::log_info(&format!(
+                "{}: {} -> {}",
+                name,
+                short_commit(&old_commit),
+                short_commit(&new_commit)
+            ));
+            for link in &package.links {
+                let locked_link = previous.and_then(|l| find_locked_link(l, &link.target_folder));
+                print_plan(
+                    &format!("glam update would change {}", link.target_folder),
+                    &install_diff(root, package, link, &new_commit, locked_link),
+                );
+            }
+        }
+        return;
+    }
+
+    let top_level = glam_packages.len();
+    let mut selected = selected;
+    let graph = resolve_dependencies(
+        root,
+        &mut glam_packages,
+        &glam_lock,
+        &mut selected,
+        DEPENDENCY_JOBS,
+        false,
+    );
+
+    for name in &selected {
+        let package_index = find_package_by_name(&glam_packages, name).unwrap();
+        select_package_links(root, &mut glam_packages[package_index], &[], interactive);
+    }
+    check_link_conflicts(&glam_packages);
+
+    let (action, applied) = protect_local_changes(
+        root,
+        glam_packages.iter().filter(|p| selected.contains(&p.name)).collect(),
+        &mut glam_lock,
+        true,
+        options.local_changes.as_deref(),
+        interactive,
+        verbose,
+    );
+
+    let mut summary = vec![];
+    for name in &selected {
+        if applied.contains(name) {
+            continue;
+        }
+
+        let package_index = find_package_by_name(&glam_packages, name).unwrap();
+        let target_package = &mut glam_packages[package_index];
+
+        utils::log_info(&format!("Updating {}...", target_package.name));
+        let previous = find_locked_package(&glam_lock, &target_package.name);
+        let old_commit = previous.map(|l| l.commit.to_string()).unwrap_or_default();
+        let merge = action == "merge";
+        let locked = install_glam_package(root, target_package, previous, true, merge, interactive, verbose);
+        summary.push((name, old_commit, locked.commit.to_string()));
+        lock_package(&mut glam_lock, locked);
+    }
 
-    utils::log_info(&format!("Updating {}...", target_package.name));
-    clone_or_fetch_package(root, target_package, verbose);
-    install_glam_package(root, "", target_package, true, true, verbose);
+    glam_packages.truncate(top_level);
+    link_dependencies(&mut glam_lock, &graph);
+    prune_dependencies(root, &mut glam_lock, &glam_packages);
 
     glam_object.packages = glam_packages;
     write_glam_file(&glam_file_path, &glam_object);
+    write_lock_file(&glam_lock_path, &glam_lock);
+
+    for (name, old_commit, new_commit) in summary {
+        if old_commit == new_commit {
+            utils::log_check(&format!("{}: {} (up to date)", name, short_commit(&new_commit)));
+        } else {
+            utils::log_check(&format!(
+                "{}: {} -> {}",
+                name,
+                short_commit(&old_commit),
+                short_commit(&new_commit)
+            ));
+        }
+    }
 }
 
-pub fn apply_changes(root: &str, verbose: bool) {
+pub fn apply_changes(root: &str, options: &ApplyOptions, interactive: bool, verbose: bool) {
     let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
     let mut glam_object = read_glam_file(&glam_file_path);
+    let mut glam_lock = read_lock_file(&glam_lock_path);
     let mut glam_packages = glam_object.packages;
 
     if glam_packages.is_empty() {
         utils::log_error("No addons to apply changes!")
     }
 
-    let options = glam_packages
+    let package_names = glam_packages
         .iter()
-        .map(|x| -> &str { &x.name })
-        .collect::<Vec<&str>>();
-
-    let ans = Select::new("Which addon you want to apply changes?", options)
-        .prompt()
-        .unwrap();
+        .map(|x| x.name.to_string())
+        .collect::<Vec<String>>();
+
+    let ans = prompt::select(
+        "Which addon you want to apply changes?",
+        package_names,
+        options.package.as_deref(),
+        "--package",
+        interactive,
+    );
 
-    let package_index = find_package_by_name(&glam_packages, ans).unwrap();
+    let package_index = find_package_by_name(&glam_packages, &ans).unwrap();
     let target_package = &mut glam_packages[package_index];
 
-    apply_package_files(root, target_package, verbose);
+    if options.dry_run {
+        for link in &target_package.links {
+            print_plan(
+                &format!("glam apply would change {}/{}", package_label(target_package), link.source_folder),
+                &apply_diff(root, target_package, link),
+            );
+        }
+        return;
+    }
+
+    apply_package_files(root, target_package, &mut glam_lock, verbose);
 
-    glam_object.packages = glam_packages;
-    write_glam_file(&glam_file_path, &glam_object);
+    if options.commit {
+        commit_package_changes(root, target_package, &mut glam_lock, options, interactive);
+
+        glam_object.packages = glam_packages;
+        write_glam_file(&glam_file_path, &glam_object);
+    }
+
+    write_lock_file(&glam_lock_path, &glam_lock);
 }
 
-fn find_package_by_link(packages: &Vec<GlamPackage>, addons_folder: &str) -> Option<usize> {
-    let mut package_index = 0;
-    let mut found_package = false;
+// Addon file edits are mirrored once they stop for this long
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
 
-    for (i, package) in packages.iter().enumerate() {
-        for (_j, link) in package.links.iter().enumerate() {
-            if link.target_folder == addons_folder {
-                package_index = i;
-                found_package = true;
+pub fn watch_packages(root: &str, name: Option<&str>, verbose: bool) {
+    let glam_object = read_glam_file(&format!("{}/.glam", root));
+
+    let packages = match name {
+        Some(name) => match find_package_by_name(&glam_object.packages, name) {
+            Some(i) => vec![&glam_object.packages[i]],
+            None => {
+                utils::log_error(&format!("No package named {}!", name));
+                exit(1);
             }
+        },
+        None => glam_object.packages.iter().collect(),
+    };
+
+    // Addon folders to watch, with the source folders their files go to
+    let mut folders = vec![];
+    for package in packages {
+        for link in &package.links {
+            let target_path = format!("{}/{}", root, link.target_folder);
+            if files::is_symlink(&target_path) {
+                utils::log_info(&format!("{} is linked to its repository already", link.target_folder));
+                continue;
+            }
+            if !Path::new(&target_path).exists() {
+                utils::log_warning(&format!("{} isn't installed", link.target_folder));
+                continue;
+            }
+
+            folders.push((
+                link.target_folder.to_string(),
+                format!("{}/{}", package_dir(root, package), link.source_folder),
+                format!("{}/{}", package_label(package), link.source_folder),
+            ));
         }
     }
 
-    if found_package {
-        return Some(package_index);
+    if folders.is_empty() {
+        utils::log_info("No addon folders to watch");
+        return;
     }
 
-    return None;    
-}
+    let (sender, receiver) = mpsc::channel();
+    let res = new_debouncer(WATCH_DEBOUNCE, sender);
+    utils::assert_result(&res, "Couldn't watch addon folders!");
+    let mut debouncer = res.unwrap();
+
+    for (target_folder, source_path, source_label) in &folders {
+        let target_path = format!("{}/{}", root, target_folder);
+
+        // Catch up with the changes made before watching
+        let changed = files::list_files(&target_path)
+            .into_iter()
+            .chain(files::list_files(source_path))
+            .collect::<BTreeSet<String>>();
+        for file in changed {
+            sync_watched_file(root, target_folder, source_path, &file, verbose);
+        }
 
-fn find_package_by_name(packages: &Vec<GlamPackage>, name: &str) -> Option<usize> {
-    let mut package_index = 0;
-    let mut found_package = false;
+        let res = debouncer.watcher().watch(Path::new(&target_path), RecursiveMode::Recursive);
+        utils::assert_result(&res, &format!("Couldn't watch {}!", target_folder));
+        utils::log_info(&format!("Watching {} for changes to apply to {}", target_folder, source_label));
+    }
 
-    for (i, package) in packages.iter().enumerate() {
-        if package.name == name {
-            package_index = i;
-            found_package = true;
+    for res in receiver {
+        let events = match res {
+            Ok(events) => events,
+            Err(e) => {
+                utils::log_error(&format!("Couldn't watch addon folders: {}", e));
+                continue;
+            }
+        };
+
+        for event in events {
+            for (target_folder, source_path, _) in &folders {
+                let file = match event.path.strip_prefix(format!("{}/{}", root, target_folder)) {
+                    Ok(file) if !file.as_os_str().is_empty() => file.to_string_lossy().to_string(),
+                    _ => continue,
+                };
+                sync_watched_file(root, target_folder, source_path, &file, verbose);
+            }
         }
     }
+}
 
-    if found_package {
-        return Some(package_index);
+// Mirror an addon file (or every file of an addon folder) into the package source folder.
+// The lock is left alone, so install and update still protect the changes until they're committed.
+fn sync_watched_file(root: &str, target_folder: &str, source_path: &str, file: &str, verbose: bool) {
+    let target_file = format!("{}/{}/{}", root, target_folder, file);
+
+    if !files::is_symlink(&target_file) && Path::new(&target_file).is_dir() {
+        let res = files::sync_file(&target_file, &format!("{}/{}", source_path, file));
+        if let Err(e) = res {
+            utils::log_error(&format!("Couldn't sync {}/{}: {}", target_folder, file, e));
+            return;
+        }
+        for inner in files::list_files(&target_file) {
+            sync_watched_file(root, target_folder, source_path, &format!("{}/{}", file, inner), verbose);
+        }
+        return;
     }
 
-    return None;
+    match files::sync_file(&target_file, &format!("{}/{}", source_path, file)) {
+        Ok(true) if Path::new(&target_file).exists() || files::is_symlink(&target_file) => {
+            utils::log_check(&format!("Applied {}/{}", target_folder, file))
+        }
+        Ok(true) => utils::log_info(&format!("Removed {}/{}", target_folder, file)),
+        Ok(false) => {
+            if verbose {
+                utils::log_info(&format!("{}/{} has no changes", target_folder, file));
+            }
+        }
+        Err(e) => utils::log_error(&format!("Couldn't sync {}/{}: {}", target_folder, file, e)),
+    }
 }
 
-fn find_package_by_repository(packages: &Vec<GlamPackage>, repo: &str) -> Option<usize> {
-    let mut package_index = 0;
-    let mut found_package = false;
+pub fn remove_repository(root: &str, name: &str, purge: bool, force: bool) {
+    let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
+    let mut glam_object = read_glam_file(&glam_file_path);
+    let mut glam_lock = read_lock_file(&glam_lock_path);
+    let mut glam_packages = glam_object.packages;
 
-    for (i, package) in packages.iter().enumerate() {
-        if package.git_repo == repo {
-            package_index = i;
-            found_package = true;
+    let package_index = match find_package_by_name(&glam_packages, name) {
+        Some(i) => i,
+        None => {
+            utils::log_error(&format!("No package named {}!", name));
+            exit(1);
+        }
+    };
+
+    let package = glam_packages.remove(package_index);
+
+    if !force {
+        let modified = modified_links(root, &package, find_locked_package(&glam_lock, &package.name));
+        if !modified.is_empty() {
+            for link in &modified {
+                utils::log_error(&format!(
+                    "{} has changes that weren't applied to the repository!",
+                    link.target_folder
+                ));
+            }
+            utils::log_info("Use glam apply to keep them, or --force to discard them.");
+            exit(1);
         }
-    }
 
-    if found_package {
-        return Some(package_index);
+        // Purging the repository loses its own work too, like edits made through symlinks
+        if purge && package.path.is_none() {
+            let git = git::backend();
+            let package_path = package_dir(root, &package);
+            let package_path = Path::new(&package_path);
+            let dirty = git.is_dirty(package_path).unwrap_or(false);
+            // Without an upstream, the commits are safe only if a remote (or the archive) has them
+            let unpushed = match git.unpushed_commits(package_path) {
+                Ok(Some(count)) => count > 0,
+                _ => !git.head_reachable(package_path, &["refs/remotes/", "refs/glam/"]).unwrap_or(false),
+            };
+
+            if dirty {
+                utils::log_error(&format!("{} has uncommitted changes!", package_label(&package)));
+            }
+            if unpushed {
+                utils::log_error(&format!("{} has commits that weren't pushed!", package_label(&package)));
+            }
+            if dirty || unpushed {
+                utils::log_info("Commit and push them first, or use --force to discard them.");
+                exit(1);
+            }
+        }
     }
 
-    return None;
-}
-
-fn list_addons(root: &str, verbose: bool) -> Vec<String> {
-    let res = utils::run_shell_command(
-        &format!("ls addons"),
-        &root,
-        verbose,
-    );
+    for link in &package.links {
+        let res = files::remove_folder(&format!("{}/{}", root, link.target_folder));
+        utils::assert_result(&res, "Couldn't remove addon folder!");
+        utils::log_info(&format!("Removed {}", link.target_folder));
+    }
+    disable_plugins(root, &package.links.iter().map(|l| l.target_folder.to_string()).collect::<Vec<String>>());
+
+    if purge && package.path.is_some() {
+        utils::log_info(&format!("Kept {}, local folders aren't purged", package_label(&package)));
+    } else if purge {
+        let res = files::remove_folder(&package_dir(root, &package));
+        utils::assert_result(&res, "Couldn't remove package repository!");
+        utils::log_info(&format!("Removed {}", package_label(&package)));
+    }
 
-    utils::assert_result(&res, "Addons folder doesn't exist!");
+    glam_lock.packages.retain(|locked| locked.name != package.name);
+    prune_dependencies(root, &mut glam_lock, &glam_packages);
 
-    let addon_folders = res.unwrap().trim().to_string();
-    let split = addon_folders.split("\n").collect::<Vec<&str>>();
-    
-    return split.iter().map(|s| s.to_string()).collect();
+    glam_object.packages = glam_packages;
+    write_glam_file(&glam_file_path, &glam_object);
+    write_lock_file(&glam_lock_path, &glam_lock);
+    utils::log_check(&format!("Removed package {}", package.name));
 }
 
-fn install_glam_package(
-    root: &str,
-    commit: &str,
-    package: &mut GlamPackage,
-    update_package: bool,
-    copy_files: bool,
-    verbose: bool,
-) {
-    // Update package folder to commit hash
-    if update_package {
-        package.commit = "latest".to_string();
+pub fn show_status(root: &str) {
+    let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
+    let glam_object = read_glam_file(&glam_file_path);
+    let glam_lock = read_lock_file(&glam_lock_path);
+    let git = git::backend();
+
+    if glam_object.packages.is_empty() {
+        utils::log_info("No addons installed");
+        return;
     }
 
-    if commit != "latest" {
-        package.commit = commit.to_string();
-    }
+    for package in &glam_object.packages {
+        let package_folder = package_label(package);
+        let package_path = package_dir(root, package);
+        let locked = find_locked_package(&glam_lock, &package.name);
 
-    // TODO Get all folders on addon
-    let res = utils::run_shell_command(
-        &format!("ls .glam.d/{}/addons", package.name),
-        &root,
-        verbose,
-    );
+        let head = git.rev_parse(Path::new(&package_path), "HEAD").unwrap_or_default();
+        match &package.path {
+            Some(path) => utils::log_info(&format!("{} ({})", package.name, path)),
+            None => utils::log_info(&format!("{} ({})", package.name, short_commit(&head))),
+        }
 
-    if res.is_err() {
-        utils::log_error("Couldn't get addon name.");
-        exit(1);
-    }
+        if !Path::new(&package_path).exists() {
+            utils::log_warning(&format!("{} doesn't exist, run glam install", package_folder));
+            continue;
+        }
 
-    if package.links.is_empty() {
-        let addon_folders = res.unwrap().trim().to_string();
-        let folders = addon_folders.split("\n").collect::<Vec<&str>>();
+        for link in &package.links {
+            let target_path = format!("{}/{}", root, link.target_folder);
+            let source_folder = format!("{}/{}", package_folder, link.source_folder);
 
-        if folders.len() == 1 {
-            package.links.push(
-                Link {
-                    target_folder: format!("addons/{}", folders[0]),
-                    source_folder: format!("addons/{}", folders[0]),
+            if !Path::new(&target_path).exists() {
+                utils::log_warning(&format!("{} isn't installed", link.target_folder));
+                continue;
+            }
+
+            // Edits on linked folders are repository changes, reported below
+            if files::is_symlink(&target_path) {
+                let linked = fs::read_link(&target_path).unwrap_or_default();
+                if linked == Path::new(&link_source(package, link)) {
+                    utils::log_check(&format!("{} links to {}", link.target_folder, source_folder));
+                } else {
+                    utils::log_warning(&format!(
+                        "{} links to {}, not {}",
+                        link.target_folder,
+                        linked.display(),
+                        source_folder
+                    ));
                 }
-            );
-        } else {
-            let ans = MultiSelect::new("Which addons you'd like to import?", folders)
-                .prompt()
-                .unwrap();
+                continue;
+            }
+            if package.mode == InstallMode::Symlink {
+                utils::log_warning(&format!("{} isn't linked yet, run glam install", link.target_folder));
+            }
 
-            if ans.len() == 0 {
-                utils::log_error("No addon selected!");
-                exit(0);
+            let source_path = format!("{}/{}", package_path, link.source_folder);
+            if files::folders_differ(&target_path, &source_path) {
+                utils::log_warning(&format!("{} differs from {}", link.target_folder, source_folder));
+            } else {
+                utils::log_check(&format!("{} matches {}", link.target_folder, source_folder));
             }
 
-            for folder in ans {
-                package.links.push(
-                    Link {
-                        target_folder: format!("addons/{}", folder),
-                        source_folder: format!("addons/{}", folder),
-                    }
-                );
+            if let Some(locked_link) = locked.and_then(|l| find_locked_link(l, &link.target_folder)) {
+                let changes = local_changes(root, locked_link);
+                if !changes.is_empty() {
+                    utils::log_warning(&format!(
+                        "{} has changes not applied: {}",
+                        link.target_folder,
+                        changes.describe()
+                    ));
+                }
             }
         }
-    }
-
-    if package.commit == "latest" {
-        let res = utils::run_shell_command(
-            &format!("cd .glam.d/{} && git rev-parse HEAD", package.name),
-            &root,
-            verbose,
-        )
-        .unwrap();
-        package.commit = res.trim().to_string();
-    } else {
-        utils::log_info("Git checkout to package commit");
-        let res = utils::run_shell_command(
-            &format!(
-                "cd .glam.d/{} && git reset --hard {}",
-                package.name, package.commit
-            ),
-            &root,
-            verbose,
-        );
 
-        utils::assert_result(&res, "Couldn't checkout repository!");
-    }
+        if git.is_dirty(Path::new(&package_path)).unwrap_or(false) {
+            utils::log_warning(&format!("{} has uncommitted changes", package_folder));
+        }
 
-    if copy_files {
-        for link in &package.links {
-            // If project addon folder doesn't exist, create it
-            let res = utils::run_shell_command(
-                &format!("mkdir -p {}", link.target_folder),
-                &root,
-                verbose,
-            );
+        match git.unpushed_commits(Path::new(&package_path)) {
+            _ if package.archive.is_some() || package.path.is_some() => (),
+            Ok(Some(0)) => (),
+            Ok(Some(count)) => {
+                utils::log_warning(&format!("{} has {} unpushed commits", package_folder, count))
+            }
+            _ => utils::log_warning(&format!("{} has no upstream branch", package_folder)),
+        }
 
-            utils::assert_result(&res, "Couldn't create addons folder!");
+        match locked {
+            _ if package.path.is_some() => (),
+            Some(locked) if locked.commit != head => utils::log_warning(&format!(
+                "{} is at {}, but the locked commit is {}",
+                package_folder,
+                short_commit(&head),
+                short_commit(&locked.commit)
+            )),
+            Some(_) => (),
+            None => utils::log_warning(&format!("{} isn't on .glam.lock", package.name)),
         }
+    }
+}
 
+pub fn show_diff(root: &str, name: &str) {
+    let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
+    let glam_object = read_glam_file(&glam_file_path);
+    let glam_lock = read_lock_file(&glam_lock_path);
+
+    let package = match find_package_by_name(&glam_object.packages, name) {
+        Some(i) => &glam_object.packages[i],
+        None => {
+            utils::log_error(&format!("No package named {}!", name));
+            exit(1);
+        }
+    };
+    let locked = find_locked_package(&glam_lock, &package.name);
+    let (commit, _) = resolve_commit(root, package, locked, false);
+
+    for link in &package.links {
+        print_plan(
+            &format!("glam apply would change {}/{}", package_label(package), link.source_folder),
+            &apply_diff(root, package, link),
+        );
+
+        let locked_link = locked.and_then(|l| find_locked_link(l, &link.target_folder));
+        print_plan(
+            &format!("glam install would change {}", link.target_folder),
+            &install_diff(root, package, link, &commit, locked_link),
+        );
+    }
+}
+
+pub fn create_patch(root: &str, name: &str) {
+    let glam_file_path = format!("{}/.glam", root);
+    let glam_lock_path = format!("{}/.glam.lock", root);
+    let glam_object = read_glam_file(&glam_file_path);
+    let glam_lock = read_lock_file(&glam_lock_path);
+
+    let package = match find_package_by_name(&glam_object.packages, name) {
+        Some(i) => &glam_object.packages[i],
+        None => {
+            utils::log_error(&format!("No package named {}!", name));
+            exit(1);
+        }
+    };
+    let locked = match find_locked_package(&glam_lock, &package.name) {
+        Some(locked) => locked,
+        None => {
+            utils::log_error(&format!("{} isn't installed, run glam install first", name));
+            exit(1);
+        }
+    };
+
+    // Changes from the locked commit to the addon folders, with project paths
+    let mut patch = String::new();
+    for link in &package.links {
+        let res = read_package_files(root, package, &locked.commit, &link.source_folder);
+        utils::assert_result(&res, "Couldn't read package files!");
+        let target = files::read_files(&format!("{}/{}", root, link.target_folder));
+
+        patch.push_str(&diff::folder_diff(
+            &res.unwrap(),
+            &target,
+            &format!("a/{}", link.target_folder),
+            &format!("b/{}", link.target_folder),
+        ));
+    }
+
+    for line in patch.lines().filter(|l| l.starts_with("Binary files ")) {
+        utils::log_warning(&format!("{}, binary changes can't be patched", line));
+    }
+
+    let patch_file = format!("{}/{}.patch", PATCHES_FOLDER, package.name);
+    let patch_path = format!("{}/{}", root, patch_file);
+
+    if patch.is_empty() {
+        if Path::new(&patch_path).exists() {
+            let res = fs::remove_file(&patch_path);
+            utils::assert_result(&res, "Couldn't remove patch file!");
+            utils::log_info(&format!("Removed {}", patch_file));
+        }
+        utils::log_info(&format!("{} has no changes to patch", name));
+        return;
+    }
+
+    let res = fs::create_dir_all(format!("{}/{}", root, PATCHES_FOLDER))
+        .and_then(|_| fs::write(&patch_path, patch));
+    utils::assert_result(&res, "Couldn't write patch file!");
+    utils::log_check(&format!("Saved changes of {} on {}", name, patch_file));
+}
+
+pub fn show_tree(root: &str, json: bool, dot: bool) {
+    let glam_object = read_glam_file(&format!("{}/.glam", root));
+    let glam_lock = read_lock_file(&format!("{}/.glam.lock", root));
+    let top_level = glam_object.packages.iter().map(|p| p.name.to_string()).collect::<Vec<String>>();
+
+    if dot {
+        println!("digraph glam {{");
+        println!("  \"{}\" [shape=box];", PROJECT_NODE);
+        for name in &top_level {
+            println!("  \"{}\" -> \"{}\";", PROJECT_NODE, name);
+        }
+        for locked in &glam_lock.packages {
+            println!(
+                "  \"{}\" [label=\"{}\\n{}\"];",
+                locked.name,
+                locked.name,
+                package_version(locked)
+            );
+            for dependency in &locked.dependencies {
+                println!("  \"{}\" -> \"{}\";", locked.name, dependency);
+            }
+        }
+        println!("}}");
+        return;
+    }
+
+    if json {
+        let nodes = top_level
+            .iter()
+            .map(|name| tree_json(&glam_lock, name, &mut vec![]))
+            .collect::<Vec<serde_json::Value>>();
+        println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "packages": nodes })).unwrap());
+        return;
+    }
+
+    if top_level.is_empty() {
+        utils::log_info("No addons installed");
+        return;
+    }
+    for name in &top_level {
+        print_tree(&glam_lock, name, "", "", &mut vec![]);
+    }
+}
+
+pub fn show_why(root: &str, name: &str, json: bool) {
+    let glam_object = read_glam_file(&format!("{}/.glam", root));
+    let glam_lock = read_lock_file(&format!("{}/.glam.lock", root));
+
+    if find_package_by_name(&glam_object.packages, name).is_none()
+        && find_locked_package(&glam_lock, name).is_none()
+    {
+        utils::log_error(&format!("No package named {}!", name));
+        exit(1);
+    }
+
+    // Every chain of dependencies from a .glam entry to the package
+    let mut paths = vec![];
+    for package in &glam_object.packages {
+        dependency_paths(&glam_lock, &package.name, name, &mut vec![], &mut paths);
+    }
+
+    if json {
+        let value = serde_json::json!({ "name": name, "paths": paths });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return;
+    }
+
+    if paths.is_empty() {
+        utils::log_info(&format!("No package on .glam needs {}", name));
+        return;
+    }
+    for path in paths {
+        if path.len() == 1 {
+            utils::log_info(&format!("{} is on .glam", name));
+        } else {
+            utils::log_info(&format!("{} is needed through {}", name, path.join(" -> ")));
+        }
+    }
+}
+
+fn find_package_by_link(packages: &[GlamPackage], addons_folder: &str) -> Option<usize> {
+    let mut package_index = 0;
+    let mut found_package = false;
+
+    for (i, package) in packages.iter().enumerate() {
+        for link in package.links.iter() {
+            if link.target_folder == addons_folder {
+                package_index = i;
+                found_package = true;
+            }
+        }
+    }
+
+    if found_package {
+        return Some(package_index);
+    }
+
+    return None;    
+}
+
+fn find_package_by_name(packages: &[GlamPackage], name: &str) -> Option<usize> {
+    let mut package_index = 0;
+    let mut found_package = false;
+
+    for (i, package) in packages.iter().enumerate() {
+        if package.name == name {
+            package_index = i;
+            found_package = true;
+        }
+    }
+
+    if found_package {
+        return Some(package_index);
+    }
+
+    return None;
+}
+
+fn find_package_by_repository(packages: &[GlamPackage], repo: &str) -> Option<usize> {
+    let mut package_index = 0;
+    let mut found_package = false;
+
+    for (i, package) in packages.iter().enumerate() {
+        if package.git_repo == repo {
+            package_index = i;
+            found_package = true;
+        }
+    }
+
+    if found_package {
+        return Some(package_index);
+    }
+
+    return None;
+}
+
+fn list_addons(root: &str) -> Vec<String> {
+    let res = files::list_folders(&format!("{}/addons", root));
+
+    utils::assert_result(&res, "Addons folder doesn't exist!");
+
+    return res.unwrap();
+}
+
+fn install_glam_package(
+    root: &str,
+    package: &mut GlamPackage,
+    locked: Option<&LockedPackage>,
+    update_package: bool,
+    merge: bool,
+    interactive: bool,
+    verbose: bool,
+) -> LockedPackage {
+    select_package_links(root, package, &[], interactive);
+
+    let git = git::backend();
+    let package_path = package_dir(root, package);
+    let (commit, tag) = resolve_commit(root, package, locked, update_package);
+
+    // Local changes get merged into the new files, with the locked ones as base
+    let mut merges = vec![];
+    if let (true, Some(locked)) = (merge, locked) {
+        for link in &package.links {
+            let locked_link = match find_locked_link(locked, &link.target_folder) {
+                Some(locked_link) => locked_link,
+                None => continue,
+            };
+
+            let res = read_package_files(root, package, &commit, &link.source_folder);
+            utils::assert_result(&res, "Couldn't read package files!");
+            let upstream = res.unwrap();
+            // Local folders have no installed version to merge from
+            let base = match package.path {
+                Some(_) => BTreeMap::new(),
+                None => git
+                    .read_files(Path::new(&package_path), &locked.commit, &link.source_folder)
+                    .unwrap_or_default(),
+            };
+
+            for file in overwritten_files(root, locked_link, &upstream) {
+                let target_file = format!("{}/{}", link.target_folder, file);
+                merges.push((
+                    target_file.to_string(),
+                    base.get(&file).cloned().unwrap_or_default(),
+                    files::read_file(&format!("{}/{}", root, target_file)).unwrap_or_default(),
+                    upstream[&file].clone(),
+                ));
+            }
+        }
+    }
+
+    // Local folders are installed as they are
+    if package.path.is_none() {
+        utils::log_info("Git checkout to package commit");
+        let res = git.reset_hard(Path::new(&package_path), &commit);
+
+        utils::assert_result(&res, "Couldn't checkout repository!");
+    }
+
+    // Copy addon repository content to target folder, or link it there
+    for link in &package.links {
+        let target_path = format!("{}/{}", root, link.target_folder);
+
+        if package.mode == InstallMode::Symlink {
+            let locked_link = locked.and_then(|l| find_locked_link(l, &link.target_folder));
+            link_addon_folder(root, package, link, locked_link);
+            continue;
+        }
+
+        // Folders linked before get a copy of their own
+        if files::is_symlink(&target_path) {
+            let res = fs::remove_file(&target_path);
+            utils::assert_result(&res, "Couldn't remove addon symlink!");
+        }
+
+        let res = files::copy_folder(
+            &format!("{}/{}", package_dir(root, package), link.source_folder),
+            &target_path,
+            verbose,
+        );
+        utils::assert_result(&res, "Couldn't copy files to addons!");
+    }
+
+    let patched = apply_package_patch(root, package, verbose);
+
+    // Record installed files, and remove the ones upstream no longer has
+    let mut links = vec![];
+    let mut plugins = vec![];
+    for link in &package.links {
+        let source_path = format!("{}/{}", package_dir(root, package), link.source_folder);
+        let mut installed = installed_files(root, &source_path, &link.target_folder);
+
+        // Files created by the package patch are installed files too
+        for file in &patched {
+            if let Some(file) = file.strip_prefix(&format!("{}/", link.target_folder)) {
+                if let Some(hash) = files::hash_file(&format!("{}/{}/{}", root, link.target_folder, file)) {
+                    installed.insert(file.to_string(), hash);
+                }
+            }
+        }
+
+        let previous = locked.and_then(|l| find_locked_link(l, &link.target_folder));
+        if let Some(previous) = previous {
+            remove_stale_files(root, previous, &installed);
+        }
+
+        // Plugins installed before keep whatever the user chose for them
+        for file in installed.keys().filter(|f| is_plugin_config(f)) {
+            if !previous.is_some_and(|p| p.files.contains_key(file)) {
+                plugins.push(format!("res://{}/{}", link.target_folder, file));
+            }
+        }
+
+        links.push(LockedLink {
+            target_folder: link.target_folder.to_string(),
+            files: installed,
+        });
+    }
+
+    for (target_file, base, ours, theirs) in merges {
+        merge_file(root, &target_file, &base, &ours, &theirs);
+    }
+
+    enable_plugins(root, &plugins);
+
+    return LockedPackage {
+        name: package.name.to_string(),
+        git_repo: package.git_repo.to_string(),
+        commit,
+        tag,
+        content_hash: content_hash(&links),
+        links,
+        dependencies: locked.map(|l| l.dependencies.clone()).unwrap_or_default(),
+    };
+}
+
+const PATCHES_FOLDER: &str = "glam-patches";
+
+const PROJECT_NODE: &str = "project";
+
+// Commit of a locked package, and the tag it was resolved from
+fn package_version(locked: &LockedPackage) -> String {
+    if locked.commit.is_empty() {
+        return "local".to_string();
+    }
+    return match &locked.tag {
+        Some(tag) => format!("{} ({})", short_commit(&locked.commit), tag),
+        None => short_commit(&locked.commit).to_string(),
+    };
+}
+
+// Print a package and its dependencies; `ancestors` stops dependency cycles
+fn print_tree(lock: &GlamLock, name: &str, prefix: &str, child_prefix: &str, ancestors: &mut Vec<String>) {
+    let locked = match find_locked_package(lock, name) {
+        Some(locked) => locked,
+        None => {
+            println!("{}{} (not installed)", prefix, name);
+            return;
+        }
+    };
+
+    if ancestors.iter().any(|a| a == name) {
+        println!("{}{} {} (cycle)", prefix, name, package_version(locked));
+        return;
+    }
+    println!("{}{} {}", prefix, name, package_version(locked));
+
+    ancestors.push(name.to_string());
+    for (i, dependency) in locked.dependencies.iter().enumerate() {
+        let last = i == locked.dependencies.len() - 1;
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        print_tree(
+            lock,
+            dependency,
+            &format!("{}{}", child_prefix, branch),
+            &format!("{}{}", child_prefix, indent),
+            ancestors,
+        );
+    }
+    ancestors.pop();
+}
+
+fn tree_json(lock: &GlamLock, name: &str, ancestors: &mut Vec<String>) -> serde_json::Value {
+    let locked = match find_locked_package(lock, name) {
+        Some(locked) => locked,
+        None => return serde_json::json!({ "name": name, "installed": false }),
+    };
+
+    let dependencies = if ancestors.iter().any(|a| a == name) {
+        vec![]
+    } else {
+        ancestors.push(name.to_string());
+        let dependencies = locked
+            .dependencies
+            .iter()
+            .map(|dependency| tree_json(lock, dependency, ancestors))
+            .collect();
+        ancestors.pop();
+        dependencies
+    };
+
+    return serde_json::json!({
+        "name": name,
+        "installed": true,
+        "git_repo": locked.git_repo,
+        "commit": locked.commit,
+        "tag": locked.tag,
+        "dependencies": dependencies,
+    });
+}
+
+fn dependency_paths(
+    lock: &GlamLock,
+    from: &str,
+    to: &str,
+    path: &mut Vec<String>,
+    paths: &mut Vec<Vec<String>>,
+) {
+    if path.iter().any(|p| p == from) {
+        return;
+    }
+
+    path.push(from.to_string());
+    if from == to {
+        paths.push(path.clone());
+    } else if let Some(locked) = find_locked_package(lock, from) {
+        for dependency in &locked.dependencies {
+            dependency_paths(lock, dependency, to, path, paths);
+        }
+    }
+    path.pop();
+}
+
+// Repositories cloned or fetched at the same time for dependencies, outside glam install
+const DEPENDENCY_JOBS: usize = 4;
+
+// Add the packages declared on the .glam files of packages, recursively, and
+// return which packages each one depends on. A repository is only installed
+// once: dependencies resolving to another commit of it are a conflict, unless
+// the project .glam file asks for it directly. Dependencies of packages being
+// updated are updated too.
+fn resolve_dependencies(
+    root: &str,
+    packages: &mut Vec<GlamPackage>,
+    lock: &GlamLock,
+    updated: &mut Vec<String>,
+    jobs: usize,
+    offline: bool,
+) -> BTreeMap<String, Vec<String>> {
+    let git = git::backend();
+    let top_level = packages.len();
+    let mut graph = BTreeMap::new();
+    let mut requests = vec![];
+    let mut scanned = 0;
+
+    while scanned < packages.len() {
+        let mut found: Vec<GlamPackage> = vec![];
+
+        for package in &packages[scanned..] {
+            let package_path = package_dir(root, package);
+            if !Path::new(&package_path).exists() {
+                continue;
+            }
+
+            let locked = find_locked_package(lock, &package.name);
+            let update = updated.contains(&package.name);
+            let (commit, _) = resolve_commit(root, package, locked, update);
+
+            let res = match package.path {
+                Some(_) => Ok(files::read_file(&format!("{}/.glam", package_path))),
+                None => git.read_file(Path::new(&package_path), &commit, ".glam"),
+            };
+            utils::assert_result(&res, "Couldn't read package .glam file!");
+            let manifest = match res.unwrap() {
+                Some(manifest) => manifest,
+                None => continue,
+            };
+            let manifest: GlamObject = match serde_json::from_slice(&manifest) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    utils::log_error(&format!("{} has an invalid .glam file: {}", package.name, e));
+                    exit(1);
+                }
+            };
+
+            let mut dependencies = vec![];
+            for mut dependency in manifest.packages {
+                let known = packages.iter().chain(found.iter());
+                let existing = known
+                    .clone()
+                    .find(|p| same_source(p, &dependency))
+                    .map(|p| p.name.to_string());
+
+                let name = match existing {
+                    Some(name) => name,
+                    None => {
+                        if let Some(other) = known.clone().find(|p| p.name == dependency.name) {
+                            utils::log_error(&format!(
+                                "{} depends on {} from {}, but {} is {}!",
+                                package.name, dependency.name, dependency.git_repo, other.name, other.git_repo
+                            ));
+                            exit(1);
+                        }
+
+                        // Reuse the addon folders picked on a previous install
+                        if let Some(locked) = find_locked_package(lock, &dependency.name) {
+                            if dependency.links.is_empty() {
+                                dependency.links = locked
+                                    .links
+                                    .iter()
+                                    .map(|l| Link {
+                                        target_folder: l.target_folder.to_string(),
+                                        source_folder: l.target_folder.to_string(),
+                                    })
+                                    .collect();
+                            }
+                        }
+
+                        if update && !updated.contains(&dependency.name) {
+                            updated.push(dependency.name.to_string());
+                        }
+                        found.push(dependency.clone());
+                        dependency.name.to_string()
+                    }
+                };
+
+                dependency.name = name.to_string();
+                requests.push((package.name.to_string(), dependency));
+                if !dependencies.contains(&name) {
+                    dependencies.push(name);
+                }
+            }
+
+            graph.insert(package.name.to_string(), dependencies);
+        }
+
+        scanned = packages.len();
+        if !found.is_empty() {
+            fetch_packages(root, &mut found, jobs, offline);
+            packages.extend(found);
+        }
+    }
+
+    // Every package asking for a repository must agree on its commit
+    let mut wanted_by: BTreeMap<String, (String, String)> = BTreeMap::new();
+    for (requester, dependency) in requests {
+        let index = find_package_by_name(packages, &dependency.name).unwrap();
+        let locked = find_locked_package(lock, &dependency.name);
+        let update = updated.contains(&dependency.name);
+        let (wanted, _) = resolve_commit(root, &dependency, locked, update);
+
+        if index < top_level {
+            let (resolved, _) = resolve_commit(root, &packages[index], locked, update);
+            if wanted != resolved {
+                utils::log_warning(&format!(
+                    "{} wants {} at {}, using {} from .glam",
+                    requester,
+                    dependency.name,
+                    short_commit(&wanted),
+                    short_commit(&resolved)
+                ));
+            }
+            continue;
+        }
+
+        match wanted_by.get(&dependency.name) {
+            Some((other, commit)) if *commit != wanted => {
+                utils::log_error(&format!(
+                    "{} wants {} at {}, but {} wants it at {}!",
+                    requester,
+                    dependency.name,
+                    short_commit(&wanted),
+                    other,
+                    short_commit(commit)
+                ));
+                utils::log_info(&format!("Add {} to .glam to pick the commit to use.", dependency.name));
+                exit(1);
+            }
+            Some(_) => (),
+            None => {
+                wanted_by.insert(dependency.name.to_string(), (requester, wanted));
+            }
+        }
+    }
+
+    return graph;
+}
+
+// Whether two packages come from the same repository or asset
+fn same_source(a: &GlamPackage, b: &GlamPackage) -> bool {
+    if a.asset_id.is_some() || b.asset_id.is_some() {
+        return a.asset_id == b.asset_id;
+    }
+    if a.path.is_some() || b.path.is_some() {
+        return a.path == b.path;
+    }
+    let normalize = |url: &str| url.trim_end_matches('/').trim_end_matches(".git").to_string();
+    return normalize(&a.git_repo) == normalize(&b.git_repo);
+}
+
+fn link_dependencies(lock: &mut GlamLock, graph: &BTreeMap<String, Vec<String>>) {
+    for locked in lock.packages.iter_mut() {
+        if let Some(dependencies) = graph.get(&locked.name) {
+            locked.dependencies = dependencies.clone();
+        }
+    }
+}
+
+// Uninstall locked dependencies no package on .glam needs anymore
+fn prune_dependencies(root: &str, lock: &mut GlamLock, packages: &[GlamPackage]) {
+    let mut needed = packages.iter().map(|p| p.name.to_string()).collect::<Vec<String>>();
+    let mut i = 0;
+    while i < needed.len() {
+        if let Some(locked) = find_locked_package(lock, &needed[i]) {
+            for dependency in &locked.dependencies {
+                if !needed.contains(dependency) {
+                    needed.push(dependency.to_string());
+                }
+            }
+        }
+        i += 1;
+    }
+
+    // Addon folders another package installs into now are left alone
+    let kept_folders = lock
+        .packages
+        .iter()
+        .filter(|l| needed.contains(&l.name))
+        .flat_map(|l| l.links.iter().map(|link| link.target_folder.to_string()))
+        .collect::<Vec<String>>();
+
+    let mut removed_folders = vec![];
+    for locked in lock.packages.iter().filter(|l| !needed.contains(&l.name)) {
+        for link in locked.links.iter().filter(|l| !kept_folders.contains(&l.target_folder)) {
+            remove_stale_files(root, link, &BTreeMap::new());
+            let _ = fs::remove_dir(format!("{}/{}", root, link.target_folder));
+            removed_folders.push(link.target_folder.to_string());
+        }
+        utils::log_info(&format!("Removed {}, no package depends on it anymore", locked.name));
+    }
+    disable_plugins(root, &removed_folders);
+
+    lock.packages.retain(|l| needed.contains(&l.name));
+}
+
+const PLUGINS_SECTION: &str = "editor_plugins";
+const PLUGINS_KEY: &str = "enabled";
+
+fn is_plugin_config(file: &str) -> bool {
+    return file == "plugin.cfg" || file.ends_with("/plugin.cfg");
+}
+
+// Enable editor plugins on project.godot, by the res:// path of their plugin.cfg
+fn enable_plugins(root: &str, plugins: &[String]) {
+    update_enabled_plugins(root, |enabled| {
+        for plugin in plugins {
+            if !enabled.contains(plugin) {
+                enabled.push(plugin.to_string());
+                utils::log_info(&format!("Enabled editor plugin {}", plugin));
+            }
+        }
+    });
+}
+
+// Disable the editor plugins inside some addon folders
+fn disable_plugins(root: &str, target_folders: &[String]) {
+    update_enabled_plugins(root, |enabled| {
+        enabled.retain(|plugin| {
+            let removed = target_folders
+                .iter()
+                .any(|folder| plugin.starts_with(&format!("res://{}/", folder)));
+            if removed {
+                utils::log_info(&format!("Disabled editor plugin {}", plugin));
+            }
+            return !removed;
+        });
+    });
+}
+
+fn update_enabled_plugins(root: &str, update: impl FnOnce(&mut Vec<String>)) {
+    let project_path = format!("{}/project.godot", root);
+    let res = fs::read_to_string(&project_path);
+    utils::assert_result(&res, "Couldn't read project.godot!");
+    let mut project = config_file::ConfigFile::parse(&res.unwrap());
+
+    // Godot 3 projects (config_version 4 and older) name the array type differently
+    let config_version = project.get("", "config_version").and_then(|v| v.trim().parse::<u32>().ok());
+    let empty_array = match config_version {
+        Some(version) if version < 5 => "PoolStringArray()",
+        _ => "PackedStringArray()",
+    };
+    let value = project.get(PLUGINS_SECTION, PLUGINS_KEY).unwrap_or(empty_array);
+    let (array_type, mut enabled) = match config_file::parse_string_array(value) {
+        Some(array) => array,
+        None => {
+            utils::log_warning("Couldn't read the enabled editor plugins of project.godot, leaving them alone");
+            return;
+        }
+    };
+
+    let previous = enabled.clone();
+    update(&mut enabled);
+    if enabled == previous {
+        return;
+    }
+
+    if enabled.is_empty() {
+        project.remove(PLUGINS_SECTION, PLUGINS_KEY);
+    } else {
+        let value = config_file::format_string_array(&array_type, &enabled);
+        project.set(PLUGINS_SECTION, PLUGINS_KEY, &value);
+    }
+
+    let res = fs::write(&project_path, project.to_string());
+    utils::assert_result(&res, "Couldn't write project.godot!");
+}
+
+// Reapply the changes saved by glam patch, returning the files it wrote
+fn apply_package_patch(root: &str, package: &GlamPackage, verbose: bool) -> Vec<String> {
+    let patch_file = format!("{}/{}.patch", PATCHES_FOLDER, package.name);
+    let text = match fs::read_to_string(format!("{}/{}", root, patch_file)) {
+        Ok(text) => text,
+        Err(_) => return vec![],
+    };
+
+    let patches = match diff::parse_patch(&text) {
+        Ok(patches) => patches,
+        Err(e) => {
+            utils::log_error(&format!("{} is invalid: {}", patch_file, e));
+            exit(1);
+        }
+    };
+
+    let mut written = vec![];
+    let mut failed = false;
+    for patch in patches {
+        let path = match patch.new_path.as_ref().or(patch.old_path.as_ref()) {
+            Some(path) => path.to_string(),
+            None => continue,
+        };
+        if !package.links.iter().any(|l| path.starts_with(&format!("{}/", l.target_folder))) {
+            utils::log_warning(&format!("{} isn't on an addon folder of {}, skipping it", path, package.name));
+            continue;
+        }
+
+        let file = format!("{}/{}", root, path);
+        let content = match patch.old_path {
+            Some(_) => fs::read(&file).map_err(|_| "file doesn't exist".to_string()),
+            None => Ok(vec![]),
+        };
+
+        match content.and_then(|content| patch.apply(&content)) {
+            Ok(Some(patched)) => {
+                let res = fs::create_dir_all(Path::new(&file).parent().unwrap())
+                    .and_then(|_| fs::write(&file, patched));
+                utils::assert_result(&res, "Couldn't write patched file!");
+                if verbose {
+                    utils::log_info(&format!("Patched {}", path));
+                }
+                written.push(path);
+            }
+            Ok(None) => {
+                let res = fs::remove_file(&file);
+                utils::assert_result(&res, "Couldn't remove patched file!");
+                if verbose {
+                    utils::log_info(&format!("Removed {}", path));
+                }
+            }
+            Err(e) => {
+                utils::log_error(&format!("{} doesn't apply to {} anymore: {}", patch_file, path, e));
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        utils::log_info(&format!(
+            "Those files were installed without the patch, fix them and run glam patch {}",
+            package.name
+        ));
+    } else {
+        utils::log_check(&format!("Applied {}", patch_file));
+    }
+
+    return written;
+}
+
+pub const LOCAL_CHANGES_ACTIONS: &[&str] = &["abort", "apply", "merge", "overwrite"];
+
+// Ask what to do when installing would overwrite addon files changed locally, or
+// changes applied to a repository that weren't committed yet.
+// Returns the action, and the packages whose changes were applied instead of installing them.
+fn protect_local_changes(
+    root: &str,
+    packages: Vec<&GlamPackage>,
+    lock: &mut GlamLock,
+    update_package: bool,
+    action: Option<&str>,
+    interactive: bool,
+    verbose: bool,
+) -> (String, Vec<String>) {
+    let git = git::backend();
+    let mut changed = vec![];
+    let mut uncommitted = vec![];
+
+    for package in packages {
+        // The checkout to the installed commit would discard them
+        if package.path.is_none() && git.is_dirty(Path::new(&package_dir(root, package))).unwrap_or(false) {
+            uncommitted.push(package_label(package));
+        }
+
+        let locked = match find_locked_package(lock, &package.name) {
+            Some(locked) => locked,
+            None => continue,
+        };
+        let (commit, _) = resolve_commit(root, package, Some(locked), update_package);
+
+        let mut overwritten = vec![];
+        for link in &package.links {
+            let locked_link = match find_locked_link(locked, &link.target_folder) {
+                Some(locked_link) => locked_link,
+                None => continue,
+            };
+
+            let res = read_package_files(root, package, &commit, &link.source_folder);
+            utils::assert_result(&res, "Couldn't read package files!");
+            for file in overwritten_files(root, locked_link, &res.unwrap()) {
+                overwritten.push(format!("{}/{}", link.target_folder, file));
+            }
+        }
+
+        if !overwritten.is_empty() {
+            changed.push((package, overwritten));
+        }
+    }
+
+    if changed.is_empty() {
+        if !uncommitted.is_empty() && action != Some("overwrite") {
+            for label in &uncommitted {
+                utils::log_error(&format!("{} has changes that weren't committed!", label));
+            }
+            utils::log_info("Use glam apply --commit to keep them, or --local-changes overwrite to discard them.");
+            exit(1);
+        }
+        return ("overwrite".to_string(), vec![]);
+    }
+
+    for (_, overwritten) in &changed {
+        for file in overwritten {
+            utils::log_warning(&format!("{} has local changes", file));
+        }
+    }
+
+    let options = LOCAL_CHANGES_ACTIONS.iter().map(|a| a.to_string()).collect();
+    let ans = prompt::select(
+        "Addon files with local changes would be overwritten, what should glam do?",
+        options,
+        action,
+        "--local-changes",
+        interactive,
+    );
+
+    let mut applied = vec![];
+    match ans.as_str() {
+        "abort" => {
+            utils::log_error("Addon files have local changes!");
+            utils::log_info("Use glam apply to keep them, or --local-changes to choose what to do.");
+            exit(1);
+        }
+        "apply" => {
+            for (package, _) in changed {
+                apply_package_files(root, package, lock, verbose);
+                utils::log_warning(&format!(
+                    "Applied local changes of {} to {}, commit them before installing it again",
+                    package.name,
+                    package_label(package)
+                ));
+                applied.push(package.name.to_string());
+            }
+        }
+        _ => (),
+    }
+
+    return (ans, applied);
+}
+
+// Locally changed files of an addon folder that would be overwritten with different content
+fn overwritten_files(root: &str, link: &LockedLink, upstream: &BTreeMap<String, Vec<u8>>) -> Vec<String> {
+    let changes = local_changes(root, link);
+
+    return changes
+        .modified
+        .into_iter()
+        .chain(changes.added)
+        .filter(|file| match upstream.get(file) {
+            Some(content) => {
+                files::read_file(&format!("{}/{}/{}", root, link.target_folder, file)).as_ref() != Some(content)
+            }
+            None => false,
+        })
+        .collect();
+}
+
+fn merge_file(root: &str, target_file: &str, base: &[u8], ours: &[u8], theirs: &[u8]) {
+    let (merged, conflicts) = match diffy::merge_bytes(base, ours, theirs) {
+        Ok(merged) => (merged, false),
+        Err(merged) => (merged, true),
+    };
+
+    let res = fs::write(format!("{}/{}", root, target_file), merged);
+    utils::assert_result(&res, "Couldn't write merged file!");
+
+    if conflicts {
+        utils::log_warning(&format!("{} has merge conflicts, resolve them by hand", target_file));
+    } else {
+        utils::log_check(&format!("Merged local changes of {}", target_file));
+    }
+}
+
+// Pick which addon folders of the package repository get installed, if not picked yet
+fn select_package_links(root: &str, package: &mut GlamPackage, links: &[String], interactive: bool) {
+    let res = files::list_folders(&format!("{}/addons", package_dir(root, package)));
+
+    if res.is_err() {
+        utils::log_error("Couldn't get addon name.");
+        exit(1);
+    }
+
+    if package.links.is_empty() {
+        let folders = res.unwrap();
+
+        if folders.len() == 1 && links.is_empty() {
+            package.links.push(
+                Link {
+                    target_folder: format!("addons/{}", folders[0]),
+                    source_folder: format!("addons/{}", folders[0]),
+                }
+            );
+        } else {
+            let ans = prompt::multi_select(
+                "Which addons you'd like to import?",
+                folders,
+                links,
+                "--links",
+                interactive,
+            );
+
+            if ans.is_empty() {
+                utils::log_error("No addon selected!");
+                exit(0);
+            }
+
+            for folder in ans {
+                package.links.push(
+                    Link {
+                        target_folder: format!("addons/{}", folder),
+                        source_folder: format!("addons/{}", folder),
+                    }
+                );
+            }
+        }
+    }
+}
+
+// Two packages can't install into the same addon folder
+fn check_link_conflicts(packages: &[GlamPackage]) {
+    let mut targets: BTreeMap<&str, &str> = BTreeMap::new();
+
+    for package in packages {
+        for link in &package.links {
+            if let Some(other) = targets.insert(&link.target_folder, &package.name) {
+                utils::log_error(&format!(
+                    "{} and {} both install into {}!",
+                    other, package.name, link.target_folder
+                ));
+                exit(1);
+            }
+        }
+    }
+}
+
+// Commit to install (and the tag it comes from): the locked one while it still
+// satisfies the manifest, otherwise the manifest revision resolved again
+fn resolve_commit(
+    root: &str,
+    package: &GlamPackage,
+    locked: Option<&LockedPackage>,
+    update_package: bool,
+) -> (String, Option<String>) {
+    // Local folders are installed as they are, with no commit
+    if package.path.is_some() {
+        return ("".to_string(), None);
+    }
+
+    let git = git::backend();
+    let package_path = package_dir(root, package);
+    let package_path = Path::new(&package_path);
+
+    let tag = package_tag(root, package);
+    let res = git.rev_parse(package_path, &package_revision(package, tag.as_deref()));
+    utils::assert_result(&res, "Couldn't resolve package commit!");
+    let resolved = res.unwrap();
+
+    if let Some(locked) = locked {
+        let pinned = !package.commit.is_empty() && package.commit != "latest";
+        let same_repo = locked.git_repo == package.git_repo;
+
+        let satisfies = if pinned || package.tag.is_some() || package.archive.is_some() {
+            resolved == locked.commit
+        } else if let Some(requirement) = &package.version {
+            // Stay on the locked tag while it's in range
+            let requirement = parse_version_requirement(requirement);
+            locked.tag.as_ref().is_some_and(|t| version::tag_matches(&requirement, t))
+        } else if package.branch.is_some() {
+            // Locked commit must still be on the branch
+            git.is_ancestor(package_path, &locked.commit, &resolved).unwrap_or(false)
+        } else {
+            true
+        };
+
+        if same_repo && satisfies && !update_package {
+            return (locked.commit.to_string(), locked.tag.clone());
+        }
+    }
+
+    return (resolved, tag);
+}
+
+// Commit an update would move a package to, from what was fetched without checking it out
+fn fetched_commit(root: &str, package: &GlamPackage, locked: Option<&LockedPackage>) -> String {
+    let (commit, tag) = resolve_commit(root, package, locked, true);
+    if package.path.is_some() || package_revision(package, tag.as_deref()) != "HEAD" {
+        return commit;
+    }
+
+    // The pull fast-forwards HEAD to its upstream, when it's behind it
+    let git = git::backend();
+    let package_path = package_dir(root, package);
+    let package_path = Path::new(&package_path);
+    return match git.rev_parse(package_path, "HEAD@{upstream}") {
+        Ok(upstream) if git.is_ancestor(package_path, &commit, &upstream).unwrap_or(false) => upstream,
+        _ => commit,
+    };
+}
+
+// Tag a package manifest asks for, directly or as the highest tag matching its version
+fn package_tag(root: &str, package: &GlamPackage) -> Option<String> {
+    if !package.commit.is_empty() && package.commit != "latest" {
+        return None;
+    }
+    if package.tag.is_some() {
+        return package.tag.clone();
+    }
+
+    let requirement = parse_version_requirement(package.version.as_ref()?);
+    let tags = package_tags(root, package);
+
+    let tag = version::highest_tag(&tags, Some(&requirement));
+    if tag.is_none() {
+        utils::log_error(&format!("No tag of {} matches version {}!", package.name, requirement));
+        exit(1);
+    }
+
+    return tag;
+}
+
+// Revision a package manifest asks for: a commit, a tag, a branch tip or the current HEAD
+fn package_revision(package: &GlamPackage, tag: Option<&str>) -> String {
+    if let Some(archive) = &package.archive {
+        return archive_reference(&archive.sha256);
+    }
+    if !package.commit.is_empty() && package.commit != "latest" {
+        return package.commit.to_string();
+    }
+    if let Some(tag) = tag {
+        return format!("refs/tags/{}", tag);
+    }
+    if let Some(branch) = &package.branch {
+        return format!("refs/remotes/origin/{}", branch);
+    }
+    return "HEAD".to_string();
+}
 
-        // TODO: Why this is throwing Err("cp: -t: No such file or directory\n") on mac?
-        // println!(
-        //     "cp -rf .glam.d/{}/{}/* -t {}",
-        //     package.name, package.source_folder, package.target_folder
-        // );
+fn package_tags(root: &str, package: &GlamPackage) -> Vec<String> {
+    let package_path = package_dir(root, package);
+    let res = git::backend().tags(Path::new(&package_path));
+    utils::assert_result(&res, "Couldn't list package tags!");
+    return res.unwrap();
+}
 
-        // Copy addon repository content to target folder
-        for link in &package.links {
-            let source_folder = &link.source_folder;
-            let target_folder = &link.target_folder;
-
-            let res = utils::run_shell_command(
-                &format!(
-                    "cp -rf .glam.d/{}/{}/* -t {}",
-                    package.name, source_folder, target_folder
-                ),
-                &root,
-                verbose,
-            );
-            utils::assert_result(&res, "Couldn't copy files to addons!");
+fn parse_version_requirement(requirement: &str) -> semver::VersionReq {
+    let res = semver::VersionReq::parse(requirement);
+    utils::assert_result(&res, &format!("Invalid version requirement {}!", requirement));
+    return res.unwrap();
+}
+
+// Move a package version requirement to the latest major release
+fn bump_version_requirement(root: &str, package: &mut GlamPackage) {
+    if package.version.is_none() {
+        return;
+    }
+
+    let tags = package_tags(root, package);
+    let latest = version::highest_tag(&tags, None).and_then(|t| version::parse_tag_version(&t));
+
+    if let Some(latest) = latest {
+        let requirement = format!("^{}", latest);
+        utils::log_info(&format!("Version requirement of {} is now {}", package.name, requirement));
+        package.version = Some(requirement);
+    }
+}
+
+fn short_commit(commit: &str) -> &str {
+    if commit.is_empty() {
+        return "none";
+    }
+    return &commit[..commit.len().min(7)];
+}
+
+fn content_hash(links: &[LockedLink]) -> String {
+    let mut hasher = Sha256::new();
+
+    for link in links {
+        for (file, hash) in &link.files {
+            hasher.update(format!("{}/{} {}\n", link.target_folder, file, hash));
         }
     }
+
+    return format!("{:x}", hasher.finalize());
 }
 
-fn apply_package_files(root: &str, package: &GlamPackage, verbose: bool) {
-    for link in &package.links {
-        // Overwrite source folder with target folder
-        let res = utils::run_shell_command(
-            &format!(
-                "for f in $(ls .glam.d/{}/{}); do rm -rf .glam.d/{}/{}/$f; done",
-                package.name, link.source_folder, package.name, link.source_folder
-            ),
-            &root,
-            verbose,
-        );
-        utils::assert_result(&res, "Couldn't overwrite source folder files!");
+/// Files of an addon folder changed since glam installed it
+#[derive(Default)]
+struct LocalChanges {
+    modified: Vec<String>,
+    added: Vec<String>,
+    removed: Vec<String>,
+}
 
-        // Copy addon repository content to target folder
-        let res = utils::run_shell_command(
-            &format!(
-                "for f in $(ls ./{}); do cp -rf ./{}/$f ./.glam.d/{}/{}/$f; done",
-                link.target_folder, link.target_folder, package.name, link.source_folder
-            ),
-            &root,
-            verbose,
-        );
+impl LocalChanges {
+    fn is_empty(&self) -> bool {
+        return self.modified.is_empty() && self.added.is_empty() && self.removed.is_empty();
+    }
 
-        utils::assert_result(&res, "Couldn't copy files to repository!");
+    fn describe(&self) -> String {
+        let modified = self.modified.iter().map(|f| format!("M {}", f));
+        let added = self.added.iter().map(|f| format!("A {}", f));
+        let removed = self.removed.iter().map(|f| format!("D {}", f));
+        return modified.chain(added).chain(removed).collect::<Vec<String>>().join(", ");
     }
 }
 
-fn clone_or_fetch_package(root: &str, package: &mut GlamPackage, verbose: bool) {
-    // If glam package folder doesn't exist, clone project
-    if !Path::new(&format!("{}/.glam.d/{}", root, package.name)).exists() {
-        let res = utils::run_shell_command(
-            &format!(
-                "cd .glam.d/ && git clone {} {} --progress",
-                package.git_repo, package.name
+// Compare an addon folder with the files recorded on the lock
+fn local_changes(root: &str, link: &LockedLink) -> LocalChanges {
+    let target_path = format!("{}/{}", root, link.target_folder);
+    let mut changes = LocalChanges::default();
+
+    for file in files::list_files(&target_path) {
+        match link.files.get(&file) {
+            Some(hash) => {
+                if files::hash_file(&format!("{}/{}", target_path, file)).as_ref() != Some(hash) {
+                    changes.modified.push(file);
+                }
+            }
+            None => changes.added.push(file),
+        }
+    }
+
+    for file in link.files.keys() {
+        if !Path::new(&format!("{}/{}", target_path, file)).exists() {
+            changes.removed.push(file.to_string());
+        }
+    }
+
+    return changes;
+}
+
+// Apply makes the source folder an exact copy of the addon folder
+fn apply_diff(root: &str, package: &GlamPackage, link: &Link) -> String {
+    let source_folder = format!("{}/{}", package_label(package), link.source_folder);
+    let source = files::read_files(&format!("{}/{}", package_dir(root, package), link.source_folder));
+    let target = files::read_files(&format!("{}/{}", root, link.target_folder));
+
+    return diff::folder_diff(&source, &target, &source_folder, &link.target_folder);
+}
+
+// Install overwrites the addon files the commit has, and removes stale ones
+// that weren't changed locally; any other addon file is left alone
+fn install_diff(
+    root: &str,
+    package: &GlamPackage,
+    link: &Link,
+    commit: &str,
+    locked: Option<&LockedLink>,
+) -> String {
+    let target_path = format!("{}/{}", root, link.target_folder);
+
+    let res = read_package_files(root, package, commit, &link.source_folder);
+    utils::assert_result(&res, "Couldn't read package files!");
+    let upstream = res.unwrap();
+
+    let mut target = files::read_files(&target_path);
+    target.retain(|file, _| {
+        upstream.contains_key(file)
+            || locked.is_some_and(|l| {
+                l.files.get(file) == files::hash_file(&format!("{}/{}", target_path, file)).as_ref()
+            })
+    });
+
+    let source_label = format!("{}/{}", package_label(package), link.source_folder);
+    return diff::folder_diff(&target, &upstream, &link.target_folder, &source_label);
+}
+
+fn print_plan(title: &str, diff: &str) {
+    if diff.is_empty() {
+        utils::log_check(&format!("{}: nothing to change", title));
+        return;
+    }
+
+    utils::log_info(&format!("{}:", title));
+    diff::print_diff(diff);
+}
+
+// Hashes of the target folder files that come from the source folder
+fn installed_files(root: &str, source_path: &str, target_folder: &str) -> BTreeMap<String, String> {
+    let mut installed = BTreeMap::new();
+
+    for file in files::list_files(source_path) {
+        let target_file = format!("{}/{}/{}", root, target_folder, file);
+        if let Some(hash) = files::hash_file(&target_file) {
+            installed.insert(file, hash);
+        }
+    }
+
+    return installed;
+}
+
+fn remove_stale_files(root: &str, link: &LockedLink, installed: &BTreeMap<String, String>) {
+    for (file, hash) in &link.files {
+        if installed.contains_key(file) {
+            continue;
+        }
+
+        let target_file = format!("{}/{}/{}", root, link.target_folder, file);
+        match files::hash_file(&target_file) {
+            Some(current_hash) if current_hash == *hash => {
+                if fs::remove_file(&target_file).is_err() {
+                    utils::log_error(&format!("Couldn't remove {}!", target_file));
+                    exit(1);
+                }
+                files::remove_empty_parents(&target_file, &format!("{}/{}", root, link.target_folder));
+                utils::log_info(&format!("Removed stale file {}/{}", link.target_folder, file));
+            }
+            Some(_) => {
+                utils::log_warning(&format!(
+                    "{}/{} was removed upstream but has local changes, keeping it",
+                    link.target_folder, file
+                ));
+            }
+            None => (),
+        }
+    }
+}
+
+// Symlink from an addon folder to its package source folder, relative when it can be
+fn link_source(package: &GlamPackage, link: &Link) -> String {
+    let source = format!("{}/{}", package_label(package), link.source_folder);
+    if Path::new(&source).is_absolute() {
+        return source;
+    }
+
+    let depth = link.target_folder.trim_end_matches('/').split('/').count() - 1;
+    return format!("{}{}", "../".repeat(depth), source);
+}
+
+// Make an addon folder a symlink to the package source folder. An existing copy
+// is only replaced when it has no local changes.
+fn link_addon_folder(root: &str, package: &GlamPackage, link: &Link, locked: Option<&LockedLink>) {
+    let target_path = format!("{}/{}", root, link.target_folder);
+    let source = link_source(package, link);
+
+    if files::is_symlink(&target_path) {
+        if fs::read_link(&target_path).is_ok_and(|linked| linked == Path::new(&source)) {
+            return;
+        }
+        let res = fs::remove_file(&target_path);
+        utils::assert_result(&res, "Couldn't remove addon symlink!");
+    } else if Path::new(&target_path).exists() {
+        let changed = match locked {
+            Some(locked) => !local_changes(root, locked).is_empty(),
+            None => files::folders_differ(
+                &target_path,
+                &format!("{}/{}", package_dir(root, package), link.source_folder),
             ),
-            &root,
-            verbose,
-        );
+        };
+        if changed {
+            utils::log_error(&format!(
+                "{} has local changes, apply or remove them before linking it!",
+                link.target_folder
+            ));
+            exit(1);
+        }
 
-        utils::assert_result(&res, "Couldn't clone repository!");
-        utils::log_check("Created package folder on .glam.d");
-    } else {
-        if package.git_repo == "" {
-            let res = utils::run_shell_command(
-                &format!("cd .glam.d/{} && git remote get-url origin", package.name),
-                &root,
-                verbose,
-            );
+        let res = files::remove_folder(&target_path);
+        utils::assert_result(&res, "Couldn't remove addon folder!");
+    }
+
+    let res = files::link_folder(&source, &target_path);
+    utils::assert_result(&res, "Couldn't link addon folder!");
+    utils::log_info(&format!("Linked {} to {}/{}", link.target_folder, package_label(package), link.source_folder));
+}
+
+// Links whose addon folder changed since it was installed. Edits on linked folders
+// are on the repository itself, so they don't count.
+fn modified_links<'a>(root: &str, package: &'a GlamPackage, locked: Option<&LockedPackage>) -> Vec<&'a Link> {
+    let mut modified = vec![];
+
+    for link in &package.links {
+        let target_path = format!("{}/{}", root, link.target_folder);
+        if !Path::new(&target_path).exists() || files::is_symlink(&target_path) {
+            continue;
+        }
+
+        let changed = match locked.and_then(|l| find_locked_link(l, &link.target_folder)) {
+            Some(locked_link) => !local_changes(root, locked_link).is_empty(),
+            // Nothing recorded, the repository files are all there is to compare with
+            None => {
+                let source_path = format!("{}/{}", package_dir(root, package), link.source_folder);
+                files::folders_differ(&target_path, &source_path)
+            }
+        };
+        if changed {
+            modified.push(link);
+        }
+    }
+
+    return modified;
+}
+
+fn apply_package_files(root: &str, package: &GlamPackage, lock: &mut GlamLock, verbose: bool) {
+    let mut locked = match find_locked_package(lock, &package.name) {
+        Some(locked) => locked.clone(),
+        None => LockedPackage {
+            name: package.name.to_string(),
+            git_repo: package.git_repo.to_string(),
+            commit: "".to_string(),
+            tag: None,
+            content_hash: "".to_string(),
+            links: vec![],
+            dependencies: vec![],
+        },
+    };
+    let previous = std::mem::take(&mut locked.links);
+
+    for link in &package.links {
+        let source_path = format!("{}/{}", package_dir(root, package), link.source_folder);
+        let target_path = format!("{}/{}", root, link.target_folder);
+
+        // Edits on linked folders are already on the repository. The installed files
+        // stay recorded, so install keeps protecting the edits until they're committed.
+        if files::is_symlink(&target_path) {
+            if verbose {
+                utils::log_info(&format!("{} is linked, nothing to copy", link.target_folder));
+            }
+            if let Some(installed) = previous.iter().find(|l| l.target_folder == link.target_folder) {
+                locked.links.push(installed.clone());
+                continue;
+            }
+        } else {
+            // Overwrite source folder with target folder
+            let res = fs::create_dir_all(&source_path).and_then(|_| files::clear_folder(&source_path));
+            utils::assert_result(&res, "Couldn't overwrite source folder files!");
+
+            // Copy addon folder content to repository source folder
+            let res = files::copy_folder(&target_path, &source_path, verbose);
+
+            utils::assert_result(&res, "Couldn't copy files to repository!");
+        }
+
+        // Addon folder and repository are in sync again
+        locked.links.push(LockedLink {
+            target_folder: link.target_folder.to_string(),
+            files: installed_files(root, &source_path, &link.target_folder),
+        });
+    }
+
+    locked.content_hash = content_hash(&locked.links);
+    lock_package(lock, locked);
+}
+
+// Commit (and push) applied changes, and pin the package to the new commit
+fn commit_package_changes(
+    root: &str,
+    package: &mut GlamPackage,
+    lock: &mut GlamLock,
+    options: &ApplyOptions,
+    interactive: bool,
+) {
+    let git = git::backend();
+    let package_folder = package_label(package);
+    let package_path = package_dir(root, package);
+    let package_path = Path::new(&package_path);
+
+    if let Some(branch) = &options.branch {
+        let res = git.create_branch(package_path, branch);
+        utils::assert_result(&res, "Couldn't create branch!");
+    }
+
+    let default_message = format!("Update {}", package.name);
+    let message = prompt::text("Commit message:", &default_message, options.message.as_deref(), interactive);
+    let folders = package
+        .links
+        .iter()
+        .map(|link| link.source_folder.to_string())
+        .collect::<Vec<String>>();
+
+    let commit = match git.commit(package_path, &folders, &message) {
+        Ok(commit) => {
+            utils::log_check(&format!("Committed {} on {}", short_commit(&commit), package_folder));
+            commit
+        }
+        Err(git::GitError::NothingToCommit) => {
+            utils::log_info("No changes to commit");
+            let res = git.rev_parse(package_path, "HEAD");
+            utils::assert_result(&res, "Couldn't resolve package commit!");
+            res.unwrap()
+        }
+        Err(e) => {
+            utils::log_error("Couldn't commit changes!");
+            utils::log_error(&format!("{}", e));
+            exit(1);
+        }
+    };
+
+    if options.push {
+        let res = git.push(package_path);
+        utils::assert_result(&res, "Couldn't push changes!");
+        utils::log_check(&format!("Pushed {} to origin", res.unwrap()));
+    }
+
+    // Local folders are installed as they are, so there's no commit to pin
+    if package.path.is_some() {
+        return;
+    }
+
+    package.commit = commit.to_string();
+
+    if let Some(locked) = find_locked_package(lock, &package.name) {
+        let mut locked = locked.clone();
+        locked.commit = commit;
+        locked.tag = None;
+        lock_package(lock, locked);
+    }
+}
+
+// Extract the package archive into a .glam.d repository, as a commit of its own
+fn fetch_archive_package(root: &str, package: &mut GlamPackage, offline: bool) -> Result<(), String> {
+    let git = git::backend();
+    let package_path = package_dir(root, package);
+    let package_path = Path::new(&package_path);
+    let archive = package.archive.as_mut().unwrap();
+
+    let extracted = |sha256: &str| {
+        return !sha256.is_empty() && git.rev_parse(package_path, &archive_reference(sha256)).is_ok();
+    };
+    if extracted(&archive.sha256) {
+        return Ok(());
+    }
+
+    let (data, sha256) = download::fetch_archive(&archive.url, &archive.sha256, offline)
+        .map_err(|e| format!("Couldn't download {}: {}", package.name, e))?;
 
-            if res.is_err() {
-                utils::log_error("GLAM Package has no origin yet!");
+    if !extracted(&sha256) {
+        if !package_path.exists() {
+            git.init(package_path)
+                .map_err(|e| format!("Couldn't create {} repository: {}", package.name, e))?;
+        }
+
+        // Extracted inside .git, the installed files only change when the commit is checked out
+        let folder = package_path.join(".git/glam-archive");
+        let _ = fs::remove_dir_all(&folder);
+        let res = download::extract_addons(&data, &folder)
+            .map_err(|e| format!("Couldn't extract {}: {}", package.name, e))
+            .and_then(|_| {
+                git.snapshot(package_path, &folder, &archive_reference(&sha256), &format!("Archive {}", sha256))
+                    .map_err(|e| format!("Couldn't commit {} archive: {}", package.name, e))
+            });
+        let _ = fs::remove_dir_all(&folder);
+        res?;
+
+        // A new repository gets the files right away, to pick its addon folders from
+        if git.rev_parse(package_path, "HEAD").is_err() {
+            git.reset_hard(package_path, &archive_reference(&sha256))
+                .map_err(|e| format!("Couldn't checkout {} archive: {}", package.name, e))?;
+        }
+    }
+
+    archive.sha256 = sha256;
+    utils::log_check(&format!("Downloaded {} archive on .glam.d", package.name));
+    return Ok(());
+}
+
+// Folder with the package files: its local path, or its .glam.d repository
+fn package_label(package: &GlamPackage) -> String {
+    return match &package.path {
+        Some(path) => path.to_string(),
+        None => format!(".glam.d/{}", package.name),
+    };
+}
+
+fn package_dir(root: &str, package: &GlamPackage) -> String {
+    let folder = package_label(package);
+    if Path::new(&folder).is_absolute() {
+        return folder;
+    }
+    return format!("{}/{}", root, folder);
+}
+
+// Files of a package folder at a commit; local folders are read as they are
+fn read_package_files(
+    root: &str,
+    package: &GlamPackage,
+    commit: &str,
+    folder: &str,
+) -> Result<BTreeMap<String, Vec<u8>>, git::GitError> {
+    let package_path = package_dir(root, package);
+    if package.path.is_some() {
+        return Ok(files::read_files(&format!("{}/{}", package_path, folder)));
+    }
+    return git::backend().read_files(Path::new(&package_path), commit, folder);
+}
+
+fn archive_reference(sha256: &str) -> String {
+    return format!("refs/glam/archives/{}", sha256);
+}
+
+// Look an asset up on the asset library, by id or name
+fn find_asset(query: &str, interactive: bool) -> asset_library::Asset {
+    let mut id = query.to_string();
+
+    if !query.chars().all(|c| c.is_ascii_digit()) {
+        let res = asset_library::search_assets(query);
+        utils::assert_result(&res, "Couldn't search the asset library!");
+        let results = res.unwrap();
+
+        let exact = results.iter().find(|a| a.title.eq_ignore_ascii_case(query));
+        id = match (exact, results.len()) {
+            (Some(asset), _) => asset.asset_id.to_string(),
+            (None, 0) => {
+                utils::log_error(&format!("No asset matches {}!", query));
                 exit(1);
             }
+            (None, 1) => results[0].asset_id.to_string(),
+            (None, _) => {
+                let options = results
+                    .iter()
+                    .map(|a| format!("{}: {}", a.asset_id, a.title))
+                    .collect();
+                let ans = prompt::select("Which asset?", options, None, "the asset id", interactive);
+                ans.split(':').next().unwrap().to_string()
+            }
+        };
+    }
+
+    let res = asset_library::get_asset(&id);
+    utils::assert_result(&res, "Couldn't get asset from the asset library!");
+    return res.unwrap();
+}
+
+// Point an asset package to the latest version of the asset
+fn refresh_asset(package: &mut GlamPackage) {
+    let asset = find_asset(package.asset_id.as_ref().unwrap(), false);
+    if package.asset_version.as_ref() == Some(&asset.version) {
+        return;
+    }
+
+    utils::log_info(&format!("{} has a new version: {}", package.name, asset.version_string));
+    package.asset_version = Some(asset.version);
+    package.archive = Some(Archive {
+        url: asset.download_url,
+        sha256: "".to_string(),
+    });
+}
+
+fn find_release_asset(git_repo: &str, release: &Release, tag: Option<&str>) -> Result<release::ReleaseAsset, String> {
+    let api_url = match &release.api_url {
+        Some(api_url) => api_url.to_string(),
+        None => release::default_api_url(&release.forge, git_repo),
+    };
+    return release::find_asset(&release.forge, &api_url, git_repo, tag, &release.asset);
+}
 
-            package.git_repo = res.unwrap().trim().to_string();
+// Point a release package to the asset of its tag, or of the latest release
+fn refresh_release(package: &mut GlamPackage) {
+    let release = package.release.as_ref().unwrap();
+    let res = find_release_asset(&package.git_repo, release, package.tag.as_deref());
+    if let Err(e) = &res {
+        utils::log_error(&format!("Couldn't find the release asset of {}: {}", package.name, e));
+        exit(1);
+    }
+    let asset = res.unwrap();
+
+    if package.archive.as_ref().is_some_and(|archive| archive.url == asset.url) {
+        return;
+    }
+
+    utils::log_info(&format!("{} has a new release: {}", package.name, asset.tag));
+    package.archive = Some(Archive {
+        url: asset.url,
+        sha256: "".to_string(),
+    });
+}
+
+fn clone_or_fetch_package(root: &str, package: &mut GlamPackage, checkout: bool) {
+    if let Err(e) = fetch_package(root, package, false, checkout) {
+        utils::log_error(&e);
+        exit(1);
+    }
+}
+
+// Clone or fetch packages concurrently, at most `jobs` at a time
+fn fetch_packages(root: &str, packages: &mut [GlamPackage], jobs: usize, offline: bool) {
+    let queue = Mutex::new(packages.iter_mut());
+    let errors = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap().next();
+                let package = match next {
+                    Some(package) => package,
+                    None => break,
+                };
+
+                if let Err(e) = fetch_package(root, package, offline, true) {
+                    errors.lock().unwrap().push(e);
+                }
+            });
+        }
+    });
+
+    let errors = errors.into_inner().unwrap();
+    if !errors.is_empty() {
+        for e in errors {
+            utils::log_error(&e);
         }
+        exit(1);
+    }
+}
+
+// Offline, packages only come from their .glam.d clone or the cache mirror as it is.
+// Without checkout, only the remote-tracking branches of an existing clone move.
+fn fetch_package(root: &str, package: &mut GlamPackage, offline: bool, checkout: bool) -> Result<(), String> {
+    if package.path.is_some() {
+        if !Path::new(&package_dir(root, package)).exists() {
+            return Err(format!("{} doesn't exist!", package_label(package)));
+        }
+        return Ok(());
+    }
+    if package.archive.is_some() {
+        return fetch_archive_package(root, package, offline);
+    }
+
+    let git = git::backend();
+    let package_path = package_dir(root, package);
+    let package_exists = Path::new(&package_path).exists();
 
-        let res = utils::run_shell_command(
-            &format!(
-                "cd .glam.d/{} && git fetch origin && git pull",
+    if package_exists && package.git_repo.is_empty() {
+        package.git_repo = git
+            .remote_url(Path::new(&package_path), "origin")
+            .map_err(|_| format!("GLAM Package {} has no origin yet!", package.name))?;
+    }
+
+    // Network access goes through the shared cache mirror, when there is one
+    let mirror = cache::mirror_path(&package.git_repo);
+    if !offline {
+        if let Some(mirror) = &mirror {
+            git.update_mirror(&package.git_repo, mirror)
+                .map_err(|e| format!("Couldn't update {} cache: {}", package.name, e))?;
+        }
+    }
+    let mirror = mirror
+        .filter(|m| m.exists())
+        .map(|m| m.to_string_lossy().to_string());
+
+    // If glam package folder doesn't exist, clone project
+    if !package_exists {
+        if offline && mirror.is_none() {
+            return Err(format!(
+                "{} isn't on .glam.d or the cache, it can't be installed offline!",
                 package.name
-            ),
-            &root,
-            verbose,
-        );
-        utils::assert_result(&res, "Couldn't fetch package repository updates!");
-        utils::log_info("Glam package folder already exists, fetched and pulled latest changes");
+            ));
+        }
+
+        let source = mirror.as_deref().unwrap_or(&package.git_repo);
+        git.clone(source, Path::new(&package_path), package.branch.as_deref())
+            .and_then(|_| git.set_remote_url(Path::new(&package_path), "origin", &package.git_repo))
+            .map_err(|e| format!("Couldn't clone {}: {}", package.name, e))?;
+
+        utils::log_check(&format!("Created {} package folder on .glam.d", package.name));
+    } else {
+        let res = match &mirror {
+            Some(mirror) if checkout => git
+                .fetch_from(Path::new(&package_path), mirror)
+                .and_then(|_| git.fast_forward(Path::new(&package_path))),
+            Some(mirror) => git.fetch_from(Path::new(&package_path), mirror),
+            None if offline => Ok(()),
+            None if checkout => git.pull(Path::new(&package_path)),
+            None => git.fetch(Path::new(&package_path)),
+        };
+        res.map_err(|e| format!("Couldn't fetch {} repository updates: {}", package.name, e))?;
+
+        if !checkout {
+            utils::log_info(&format!("Fetched latest changes of {}", package.name));
+            return Ok(());
+        }
+
+        if let Some(branch) = &package.branch {
+            git.checkout(Path::new(&package_path), branch)
+                .map_err(|e| format!("Couldn't checkout {} branch {}: {}", package.name, branch, e))?;
+        }
+
+        utils::log_info(&format!("Fetched and pulled latest changes of {}", package.name));
+    }
+
+    return Ok(());
+}
+
+fn check_locked_commits(root: &str, packages: &[GlamPackage], lock: &GlamLock) {
+    let git = git::backend();
+    let mut missing = false;
+
+    for package in packages.iter().filter(|p| p.path.is_none()) {
+        if let Some(locked) = find_locked_package(lock, &package.name) {
+            let package_path = package_dir(root, package);
+            if git.rev_parse(Path::new(&package_path), &locked.commit).is_err() {
+                utils::log_error(&format!(
+                    "Locked commit {} of {} isn't available offline!",
+                    short_commit(&locked.commit),
+                    package.name
+                ));
+                missing = true;
+            }
+        }
+    }
+
+    if missing {
+        exit(1);
     }
 }
 
@@ -582,3 +2844,198 @@ fn write_glam_file(file_path: &str, glam_object: &GlamObject) {
     let json_string = serde_json::to_string_pretty(glam_object).unwrap();
     fs::write(file_path, json_string).expect("Couldn't create .glam file!");
 }
+
+fn read_lock_file(file_path: &str) -> GlamLock {
+    if !Path::new(file_path).exists() {
+        return GlamLock::default();
+    }
+
+    let lock_content = fs::read_to_string(file_path).expect("Couldn't read .glam.lock file!");
+    let lock_obj: GlamLock = serde_json::from_str(&lock_content).unwrap();
+
+    return lock_obj;
+}
+
+fn write_lock_file(file_path: &str, glam_lock: &GlamLock) {
+    let json_string = serde_json::to_string_pretty(glam_lock).unwrap();
+    fs::write(file_path, json_string).expect("Couldn't create .glam.lock file!");
+}
+
+fn find_locked_package<'a>(lock: &'a GlamLock, name: &str) -> Option<&'a LockedPackage> {
+    return lock.packages.iter().find(|locked| locked.name == name);
+}
+
+fn find_locked_link<'a>(locked: &'a LockedPackage, target_folder: &str) -> Option<&'a LockedLink> {
+    return locked.links.iter().find(|link| link.target_folder == target_folder);
+}
+
+// Insert or replace a package on the lock
+fn lock_package(lock: &mut GlamLock, locked: LockedPackage) {
+    match lock.packages.iter().position(|p| p.name == locked.name) {
+        Some(i) => lock.packages[i] = locked,
+        None => lock.packages.push(locked),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    fn write_file(root: &str, relative: &str, content: &str) {
+        let file = Path::new(root).join(relative);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, content).unwrap();
+    }
+
+    fn hash(content: &str) -> String {
+        return format!("{:x}", Sha256::digest(content.as_bytes()));
+    }
+
+    fn locked_link(files: &[(&str, &str)]) -> LockedLink {
+        return LockedLink {
+            target_folder: "addons/foo".to_string(),
+            files: files
+                .iter()
+                .map(|(file, content)| (file.to_string(), hash(content)))
+                .collect(),
+        };
+    }
+
+    #[test]
+    fn installed_files_come_from_source() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        write_file(&root, ".glam.d/foo/addons/foo/a.gd", "one\n");
+        write_file(&root, ".glam.d/foo/addons/foo/sub/b.gd", "two\n");
+        write_file(&root, "addons/foo/a.gd", "one\n");
+        write_file(&root, "addons/foo/sub/b.gd", "two\n");
+        write_file(&root, "addons/foo/mine.gd", "mine\n");
+
+        let installed = installed_files(
+            &root,
+            &format!("{}/.glam.d/foo/addons/foo", root),
+            "addons/foo",
+        );
+        assert_eq!(
+            installed,
+            locked_link(&[("a.gd", "one\n"), ("sub/b.gd", "two\n")]).files
+        );
+    }
+
+    #[test]
+    fn stale_files_are_removed_unless_changed() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        write_file(&root, "addons/foo/kept.gd", "kept\n");
+        write_file(&root, "addons/foo/stale/stale.gd", "stale\n");
+        write_file(&root, "addons/foo/edited.gd", "edited locally\n");
+        write_file(&root, "addons/foo/mine.gd", "mine\n");
+
+        let link = locked_link(&[
+            ("kept.gd", "kept\n"),
+            ("stale/stale.gd", "stale\n"),
+            ("edited.gd", "edited\n"),
+            ("gone.gd", "gone\n"),
+        ]);
+        let installed = locked_link(&[("kept.gd", "kept\n")]).files;
+        remove_stale_files(&root, &link, &installed);
+
+        // Removed with its folder, the file still had the installed content
+        assert!(!Path::new(&format!("{}/addons/foo/stale", root)).exists());
+        // Edited and user-added files stay
+        assert_eq!(
+            fs::read_to_string(format!("{}/addons/foo/edited.gd", root)).unwrap(),
+            "edited locally\n"
+        );
+        assert!(Path::new(&format!("{}/addons/foo/mine.gd", root)).exists());
+        assert!(Path::new(&format!("{}/addons/foo/kept.gd", root)).exists());
+    }
+
+    fn upstream(files: &[(&str, &str)]) -> BTreeMap<String, Vec<u8>> {
+        return files
+            .iter()
+            .map(|(file, content)| (file.to_string(), content.as_bytes().to_vec()))
+            .collect();
+    }
+
+    #[test]
+    fn local_changes_against_lock() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        write_file(&root, "addons/foo/same.gd", "same\n");
+        write_file(&root, "addons/foo/edited.gd", "edited locally\n");
+        write_file(&root, "addons/foo/mine.gd", "mine\n");
+
+        let link = locked_link(&[
+            ("same.gd", "same\n"),
+            ("edited.gd", "edited\n"),
+            ("deleted.gd", "deleted\n"),
+        ]);
+        let changes = local_changes(&root, &link);
+        assert_eq!(changes.modified, vec!["edited.gd"]);
+        assert_eq!(changes.added, vec!["mine.gd"]);
+        assert_eq!(changes.removed, vec!["deleted.gd"]);
+        assert_eq!(changes.describe(), "M edited.gd, A mine.gd, D deleted.gd");
+
+        let link = locked_link(&[
+            ("same.gd", "same\n"),
+            ("edited.gd", "edited locally\n"),
+            ("mine.gd", "mine\n"),
+        ]);
+        assert!(local_changes(&root, &link).is_empty());
+    }
+
+    #[test]
+    fn only_changed_files_are_overwritten() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        write_file(&root, "addons/foo/edited.gd", "edited locally\n");
+        write_file(&root, "addons/foo/fixed.gd", "fixed upstream too\n");
+        write_file(&root, "addons/foo/mine.gd", "mine\n");
+        write_file(&root, "addons/foo/clash.gd", "mine\n");
+        write_file(&root, "addons/foo/same.gd", "same\n");
+
+        let link = locked_link(&[
+            ("edited.gd", "edited\n"),
+            ("fixed.gd", "fixed\n"),
+            ("same.gd", "same\n"),
+        ]);
+        let upstream = upstream(&[
+            ("edited.gd", "edited upstream\n"),
+            // Already has the local content, nothing gets lost
+            ("fixed.gd", "fixed upstream too\n"),
+            ("clash.gd", "added upstream\n"),
+            ("same.gd", "changed upstream\n"),
+        ]);
+
+        // User-added files are only overwritten when upstream adds them too
+        assert_eq!(
+            overwritten_files(&root, &link, &upstream),
+            vec!["edited.gd", "clash.gd"]
+        );
+    }
+
+    #[test]
+    fn merge_local_changes() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        write_file(&root, "addons/foo/a.gd", "");
+
+        merge_file(
+            &root,
+            "addons/foo/a.gd",
+            b"one\ntwo\nthree\n",
+            b"ONE\ntwo\nthree\n",
+            b"one\ntwo\nTHREE\n",
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}/addons/foo/a.gd", root)).unwrap(),
+            "ONE\ntwo\nTHREE\n"
+        );
+
+        merge_file(&root, "addons/foo/a.gd", b"one\n", b"mine\n", b"theirs\n");
+        let merged = fs::read_to_string(format!("{}/addons/foo/a.gd", root)).unwrap();
+        assert!(merged.contains("<<<<<<<") && merged.contains("mine") && merged.contains("theirs"));
+    }
+}
@@ -0,0 +1,442 @@
+// Godot ConfigFile syntax (project.godot, plugin.cfg...), edited in place: only the
+// values that are set or removed change, comments, formatting and line endings are
+// kept as they are
+
+enum Item {
+    /// Section header, like [editor_plugins]
+    Section { name: String, line: String },
+    /// A key (spacing included) and its value, which may span several lines
+    Entry { key: String, value: String },
+    /// Blank lines and comments
+    Other(String),
+}
+
+/// An item and the line ending that follows it
+struct Line {
+    item: Item,
+    end: String,
+}
+
+pub struct ConfigFile {
+    lines: Vec<Line>,
+    /// Line ending of new lines, the first one of the file
+    newline: String,
+    /// Whether the last line ends with a line ending
+    final_newline: bool,
+}
+
+impl ConfigFile {
+    pub fn parse(text: &str) -> ConfigFile {
+        let newline = text
+            .find('\n')
+            .map(|i| {
+                if text[..i].ends_with('\r') {
+                    "\r\n"
+                } else {
+                    "\n"
+                }
+            })
+            .unwrap_or("\n")
+            .to_string();
+        let final_newline = text.is_empty() || text.ends_with('\n');
+
+        let mut config = ConfigFile {
+            lines: vec![],
+            newline,
+            final_newline,
+        };
+        let mut lines = text.split_inclusive('\n').map(split_line_end);
+
+        while let Some((line, end)) = lines.next() {
+            let trimmed = line.trim();
+
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                let section = Item::Section {
+                    name: trimmed[1..trimmed.len() - 1].to_string(),
+                    line: line.to_string(),
+                };
+                config.push(section, end);
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) if !trimmed.starts_with(';') && !trimmed.starts_with('#') => {
+                    (key, value)
+                }
+                _ => {
+                    config.push(Item::Other(line.to_string()), end);
+                    continue;
+                }
+            };
+
+            // Arrays, dictionaries and strings go on until they are closed
+            let mut value = value.to_string();
+            let mut end = end;
+            while !is_complete(&value) {
+                match lines.next() {
+                    Some((next, next_end)) => {
+                        value.push_str(end);
+                        value.push_str(next);
+                        end = next_end;
+                    }
+                    None => break,
+                }
+            }
+
+            let entry = Item::Entry {
+                key: key.to_string(),
+                value,
+            };
+            config.push(entry, end);
+        }
+
+        return config;
+    }
+
+    // Add an item at the end, the last line of the file may not have a line ending
+    fn push(&mut self, item: Item, end: &str) {
+        let end = if end.is_empty() { &self.newline } else { end };
+        self.lines.push(Line {
+            item,
+            end: end.to_string(),
+        });
+    }
+
+    fn line(&self, item: Item) -> Line {
+        return Line {
+            item,
+            end: self.newline.to_string(),
+        };
+    }
+
+    /// Raw value of a key, as written on the file. Keys before the first section
+    /// (like config_version) are on the "" section.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let index = self.find(section, key)?;
+        return match &self.lines[index].item {
+            Item::Entry { value, .. } => Some(value),
+            _ => None,
+        };
+    }
+
+    /// Set the raw value of a key, adding the key (and its section) when missing
+    pub fn set(&mut self, section: &str, key: &str, new_value: &str) {
+        if let Some(index) = self.find(section, key) {
+            if let Item::Entry { value, .. } = &mut self.lines[index].item {
+                *value = new_value.to_string();
+            }
+            return;
+        }
+
+        let entry = self.line(Item::Entry {
+            key: key.to_string(),
+            value: new_value.to_string(),
+        });
+
+        match self.section_range(section) {
+            Some((_, end)) => {
+                // After the last entry of the section, before the blank lines that follow it
+                let mut index = end;
+                while index > 0 && self.lines[index - 1].is_blank() {
+                    index -= 1;
+                }
+                self.lines.insert(index, entry);
+            }
+            None => {
+                if self.lines.last().is_some_and(|line| !line.is_blank()) {
+                    self.lines.push(self.line(Item::Other("".to_string())));
+                }
+                self.lines.push(self.line(Item::Section {
+                    name: section.to_string(),
+                    line: format!("[{}]", section),
+                }));
+                self.lines.push(self.line(Item::Other("".to_string())));
+                self.lines.push(entry);
+            }
+        }
+    }
+
+    /// Remove a key, and its section when nothing else is left on it
+    pub fn remove(&mut self, section: &str, key: &str) {
+        let index = match self.find(section, key) {
+            Some(index) => index,
+            None => return,
+        };
+        self.lines.remove(index);
+
+        let (start, end) = match self.section_range(section) {
+            Some(range) if !section.is_empty() => range,
+            _ => return,
+        };
+        if !self.lines[start + 1..end].iter().all(Line::is_blank) {
+            return;
+        }
+
+        let last = end == self.lines.len();
+        self.lines.drain(start..end);
+        // The blank line that separated the last section from the previous one goes too
+        while last && self.lines.last().is_some_and(Line::is_blank) {
+            self.lines.pop();
+        }
+    }
+
+    fn find(&self, section: &str, key: &str) -> Option<usize> {
+        let (start, end) = self.section_range(section)?;
+        return (start..end).find(
+            |&i| matches!(&self.lines[i].item, Item::Entry { key: k, .. } if k.trim() == key),
+        );
+    }
+
+    // Items of a section, from its header to the next section
+    fn section_range(&self, section: &str) -> Option<(usize, usize)> {
+        let next_section = |start: usize| {
+            self.lines[start..]
+                .iter()
+                .position(|line| matches!(line.item, Item::Section { .. }))
+                .map(|i| start + i)
+                .unwrap_or(self.lines.len())
+        };
+
+        if section.is_empty() {
+            return Some((0, next_section(0)));
+        }
+
+        let start = self
+            .lines
+            .iter()
+            .position(|line| matches!(&line.item, Item::Section { name, .. } if name == section))?;
+        return Some((start, next_section(start + 1)));
+    }
+}
+
+impl Line {
+    fn is_blank(&self) -> bool {
+        return matches!(&self.item, Item::Other(line) if line.trim().is_empty());
+    }
+}
+
+impl std::fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            match &line.item {
+                Item::Section { line, .. } => write!(f, "{}", line)?,
+                Item::Entry { key, value } => write!(f, "{}={}", key, value)?,
+                Item::Other(line) => write!(f, "{}", line)?,
+            }
+            if self.final_newline || i + 1 < self.lines.len() {
+                write!(f, "{}", line.end)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+// A line without its line ending, and the line ending
+fn split_line_end(line: &str) -> (&str, &str) {
+    let content = match line.strip_suffix('\n') {
+        Some(content) => content.strip_suffix('\r').unwrap_or(content),
+        None => line,
+    };
+    return (content, &line[content.len()..]);
+}
+
+// Whether every bracket and string of a value is closed
+fn is_complete(value: &str) -> bool {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => (),
+        }
+    }
+
+    return !in_string && depth <= 0;
+}
+
+/// Type and strings of a PackedStringArray (or Godot 3 PoolStringArray) value
+pub fn parse_string_array(value: &str) -> Option<(String, Vec<String>)> {
+    let (array_type, inner) = value.trim().split_once('(')?;
+    if array_type != "PackedStringArray" && array_type != "PoolStringArray" {
+        return None;
+    }
+    let inner = inner.strip_suffix(')')?;
+
+    let mut strings = vec![];
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+
+        let mut string = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => string.push(chars.next()?),
+                '"' => break,
+                _ => string.push(c),
+            }
+        }
+        strings.push(string);
+    }
+
+    return Some((array_type.to_string(), strings));
+}
+
+pub fn format_string_array(array_type: &str, strings: &[String]) -> String {
+    let strings = strings
+        .iter()
+        .map(|s| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<String>>();
+    return format!("{}({})", array_type, strings.join(", "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = "; Engine configuration file.
+; Comments = are kept
+
+config_version=5
+
+[application]
+
+config/name=\"Project\"
+config/features=PackedStringArray(\"4.2\", \"Forward Plus\")
+
+[autoload]
+
+Global=\"*res://global.gd\"
+";
+
+    fn round_trip(text: &str, section: &str, key: &str, value: &str) -> String {
+        let mut config = ConfigFile::parse(text);
+        config.set(section, key, value);
+        let changed = config.to_string();
+        config.remove(section, key);
+        assert_eq!(config.to_string(), text);
+        return changed;
+    }
+
+    #[test]
+    fn parse_keeps_text() {
+        assert_eq!(ConfigFile::parse(PROJECT).to_string(), PROJECT);
+        assert_eq!(ConfigFile::parse("").to_string(), "");
+    }
+
+    #[test]
+    fn set_new_section_and_remove() {
+        let changed = round_trip(PROJECT, "editor_plugins", "enabled", "PackedStringArray()");
+        assert!(changed.ends_with(
+            "Global=\"*res://global.gd\"\n\n[editor_plugins]\n\nenabled=PackedStringArray()\n"
+        ));
+    }
+
+    #[test]
+    fn set_existing_section_and_remove() {
+        let changed = round_trip(
+            PROJECT,
+            "application",
+            "run/main_scene",
+            "\"res://main.tscn\"",
+        );
+        assert!(changed.contains("config/features=PackedStringArray(\"4.2\", \"Forward Plus\")\nrun/main_scene=\"res://main.tscn\"\n\n[autoload]"));
+    }
+
+    #[test]
+    fn set_replaces_value() {
+        let mut config = ConfigFile::parse(PROJECT);
+        config.set("autoload", "Global", "\"*res://other.gd\"");
+        assert_eq!(config.to_string(), PROJECT.replace("global.gd", "other.gd"));
+    }
+
+    #[test]
+    fn comments_are_not_keys() {
+        let config = ConfigFile::parse(PROJECT);
+        assert_eq!(config.get("", "; Comments"), None);
+        assert_eq!(config.get("", "config_version"), Some("5"));
+        assert_eq!(
+            config.get("application", "config/name"),
+            Some("\"Project\"")
+        );
+    }
+
+    #[test]
+    fn multi_line_values() {
+        let text =
+            "[input]\n\nui_up={\n\"deadzone\": 0.5,\n\"events\": [\"(\", \"]\"]\n}\nui_down=1\n";
+        let mut config = ConfigFile::parse(text);
+        assert_eq!(
+            config.get("input", "ui_up"),
+            Some("{\n\"deadzone\": 0.5,\n\"events\": [\"(\", \"]\"]\n}")
+        );
+        assert_eq!(config.get("input", "ui_down"), Some("1"));
+
+        config.remove("input", "ui_down");
+        assert_eq!(
+            config.to_string(),
+            "[input]\n\nui_up={\n\"deadzone\": 0.5,\n\"events\": [\"(\", \"]\"]\n}\n"
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let text = PROJECT.replace('\n', "\r\n");
+        let changed = round_trip(&text, "editor_plugins", "enabled", "PackedStringArray()");
+        assert!(
+            changed.ends_with("\r\n\r\n[editor_plugins]\r\n\r\nenabled=PackedStringArray()\r\n")
+        );
+
+        let text = "[input]\r\n\r\nui_up={\r\n\"deadzone\": 0.5\r\n}\r\n";
+        let config = ConfigFile::parse(text);
+        assert_eq!(
+            config.get("input", "ui_up"),
+            Some("{\r\n\"deadzone\": 0.5\r\n}")
+        );
+        assert_eq!(config.to_string(), text);
+    }
+
+    #[test]
+    fn missing_final_newline() {
+        let text = PROJECT.trim_end();
+        let changed = round_trip(text, "editor_plugins", "enabled", "PackedStringArray()");
+        assert!(changed.ends_with("\n\n[editor_plugins]\n\nenabled=PackedStringArray()"));
+        round_trip(text, "autoload", "Other", "\"*res://other.gd\"");
+    }
+
+    #[test]
+    fn string_arrays() {
+        let value = "PoolStringArray( \"res://addons/a/plugin.cfg\", \"res://addons/\\\"b\\\"/plugin.cfg\" )";
+        let (array_type, strings) = parse_string_array(value).unwrap();
+        assert_eq!(array_type, "PoolStringArray");
+        assert_eq!(
+            strings,
+            vec!["res://addons/a/plugin.cfg", "res://addons/\"b\"/plugin.cfg"]
+        );
+        assert_eq!(
+            format_string_array(&array_type, &strings),
+            "PoolStringArray(\"res://addons/a/plugin.cfg\", \"res://addons/\\\"b\\\"/plugin.cfg\")"
+        );
+
+        assert_eq!(
+            parse_string_array("PackedStringArray()"),
+            Some(("PackedStringArray".to_string(), vec![]))
+        );
+        assert_eq!(parse_string_array("[\"a\"]"), None);
+    }
+}
@@ -0,0 +1,248 @@
+use colored::Colorize;
+use diffy::DiffOptions;
+use std::collections::BTreeMap;
+
+// Unified diff turning the files of one folder into the files of another.
+// Files are keyed by relative path; labels prefix the paths on the headers.
+pub fn folder_diff(
+    old: &BTreeMap<String, Vec<u8>>,
+    new: &BTreeMap<String, Vec<u8>>,
+    old_label: &str,
+    new_label: &str,
+) -> String {
+    let mut paths = old.keys().chain(new.keys()).collect::<Vec<&String>>();
+    paths.sort();
+    paths.dedup();
+
+    let mut diff = String::new();
+    for path in paths {
+        let old_content = old.get(path);
+        let new_content = new.get(path);
+        if old_content == new_content {
+            continue;
+        }
+
+        let old_header = match old_content {
+            Some(_) => format!("{}/{}", old_label, path),
+            None => "/dev/null".to_string(),
+        };
+        let new_header = match new_content {
+            Some(_) => format!("{}/{}", new_label, path),
+            None => "/dev/null".to_string(),
+        };
+
+        let old_text = std::str::from_utf8(old_content.map_or(&[][..], |c| c));
+        let new_text = std::str::from_utf8(new_content.map_or(&[][..], |c| c));
+        match (old_text, new_text) {
+            (Ok(old_text), Ok(new_text)) => {
+                let patch = DiffOptions::new()
+                    .set_original_filename(old_header)
+                    .set_modified_filename(new_header)
+                    .create_patch(old_text, new_text);
+                diff.push_str(&patch.to_string());
+            }
+            _ => {
+                diff.push_str(&format!(
+                    "Binary files {} and {} differ\n",
+                    old_header, new_header
+                ));
+            }
+        }
+    }
+
+    return diff;
+}
+
+/// Changes to one file of a patch made by folder_diff
+pub struct FilePatch {
+    /// Path before the change, None for new files
+    pub old_path: Option<String>,
+    /// Path after the change, None for deleted files
+    pub new_path: Option<String>,
+    hunks: String,
+}
+
+impl FilePatch {
+    // Patched file content, or None when the patch deletes the file
+    pub fn apply(&self, content: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let content = std::str::from_utf8(content).map_err(|_| "file isn't text".to_string())?;
+        let patch = diffy::Patch::from_str(&self.hunks).map_err(|e| e.to_string())?;
+        let patched = diffy::apply(content, &patch).map_err(|e| e.to_string())?;
+
+        if self.new_path.is_none() {
+            return Ok(None);
+        }
+        return Ok(Some(patched.into_bytes()));
+    }
+}
+
+// Split a unified diff of several files, skipping lines that aren't part of one
+// (like "Binary files ... differ")
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines = text.split_inclusive('\n').collect::<Vec<&str>>();
+    let mut patches: Vec<FilePatch> = vec![];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")) {
+            patches.push(FilePatch {
+                old_path: patch_path(&line[4..]),
+                new_path: patch_path(&lines[i + 1][4..]),
+                hunks: String::new(),
+            });
+            i += 2;
+            continue;
+        }
+
+        if line.starts_with("@@ ") {
+            let patch = match patches.last_mut() {
+                Some(patch) => patch,
+                None => return Err(format!("hunk without file header on line {}", i + 1)),
+            };
+            let (mut old_lines, mut new_lines) = hunk_lengths(line)
+                .ok_or_else(|| format!("invalid hunk header on line {}", i + 1))?;
+
+            patch.hunks.push_str(line);
+            i += 1;
+            while i < lines.len() && (old_lines > 0 || new_lines > 0 || lines[i].starts_with('\\'))
+            {
+                match lines[i].chars().next() {
+                    Some(' ') | Some('\n') => {
+                        old_lines = old_lines.saturating_sub(1);
+                        new_lines = new_lines.saturating_sub(1);
+                    }
+                    Some('-') => old_lines = old_lines.saturating_sub(1),
+                    Some('+') => new_lines = new_lines.saturating_sub(1),
+                    Some('\\') => (),
+                    _ => return Err(format!("invalid hunk line {}", i + 1)),
+                }
+                patch.hunks.push_str(lines[i]);
+                i += 1;
+            }
+            continue;
+        }
+
+        i += 1;
+    }
+
+    return Ok(patches);
+}
+
+// Path on a "---" or "+++" header, without the a/ or b/ prefix
+fn patch_path(header: &str) -> Option<String> {
+    let path = header.trim_end_matches(['\n', '\r']);
+    let path = path.split('\t').next().unwrap_or(path);
+    if path == "/dev/null" {
+        return None;
+    }
+
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    return Some(path.to_string());
+}
+
+// Old and new line counts of a hunk header like "@@ -1,3 +1,4 @@"
+fn hunk_lengths(header: &str) -> Option<(usize, usize)> {
+    let mut ranges = header.split_whitespace().skip(1);
+    let old = ranges.next()?.strip_prefix('-')?;
+    let new = ranges.next()?.strip_prefix('+')?;
+
+    let length = |range: &str| match range.split_once(',') {
+        Some((_, length)) => length.parse::<usize>().ok(),
+        None => Some(1),
+    };
+    return Some((length(old)?, length(new)?));
+}
+
+pub fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(files: &[(&str, &str)]) -> BTreeMap<String, Vec<u8>> {
+        return files
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.as_bytes().to_vec()))
+            .collect();
+    }
+
+    #[test]
+    fn patch_round_trip() {
+        let old = folder(&[
+            ("changed.gd", "extends Node\n\nfunc _ready():\n\tpass\n"),
+            ("deleted.gd", "extends Node\n"),
+            ("same.gd", "extends Node\n"),
+            ("sub/no newline.gd", "one\ntwo"),
+        ]);
+        let new = folder(&[
+            ("added.gd", "extends Control\n"),
+            (
+                "changed.gd",
+                "extends Node\n\nfunc _ready():\n\tprint(\"ready\")\n",
+            ),
+            ("same.gd", "extends Node\n"),
+            ("sub/no newline.gd", "one\nthree"),
+        ]);
+
+        let diff = folder_diff(&old, &new, "a", "b");
+        assert!(diff.contains("--- /dev/null\n+++ b/added.gd\n"));
+        assert!(diff.contains("--- a/deleted.gd\n+++ /dev/null\n"));
+        assert!(diff.contains("\\ No newline at end of file"));
+        assert!(!diff.contains("same.gd"));
+
+        let patches = parse_patch(&diff).unwrap();
+        assert_eq!(patches.len(), 4);
+
+        let mut patched = old.clone();
+        for patch in &patches {
+            let path = patch.old_path.as_ref().or(patch.new_path.as_ref()).unwrap();
+            let content = old.get(path).cloned().unwrap_or_default();
+            match patch.apply(&content).unwrap() {
+                Some(content) => patched.insert(patch.new_path.clone().unwrap(), content),
+                None => patched.remove(path),
+            };
+        }
+        assert_eq!(patched, new);
+    }
+
+    #[test]
+    fn parse_patch_skips_other_lines() {
+        let text = "Binary files a/icon.png and b/icon.png differ\n--- a/a.gd\t2024-01-01\n+++ b/a.gd\n@@ -1 +1 @@\n-one\n+two\n";
+        let patches = parse_patch(text).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].old_path.as_deref(), Some("a.gd"));
+        assert_eq!(patches[0].apply(b"one\n").unwrap(), Some(b"two\n".to_vec()));
+    }
+
+    #[test]
+    fn parse_patch_errors() {
+        assert!(parse_patch("@@ -1 +1 @@\n-one\n+two\n").is_err());
+        assert!(parse_patch("--- a/a.gd\n+++ b/a.gd\n@@ -1 +1 @@\n?one\n").is_err());
+        assert!(parse_patch("--- a/a.gd\n+++ b/a.gd\n@@ one @@\n").is_err());
+    }
+
+    #[test]
+    fn apply_fails_on_changed_file() {
+        let patches = parse_patch("--- a/a.gd\n+++ b/a.gd\n@@ -1 +1 @@\n-one\n+two\n").unwrap();
+        assert!(patches[0].apply(b"three\n").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+use super::cache;
+
+pub fn download(url: &str) -> Result<Vec<u8>, String> {
+    let response = ureq::get(url).call().map_err(|e| e.to_string())?;
+    let mut data = vec![];
+    response
+        .into_reader()
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    return Ok(data);
+}
+
+pub fn get_json(url: &str, query: &[(&str, &str)]) -> Result<serde_json::Value, String> {
+    let mut request = ureq::get(url);
+    for (key, value) in query {
+        request = request.query(key, value);
+    }
+
+    let response = request.call().map_err(|e| e.to_string())?;
+    let body = response.into_string().map_err(|e| e.to_string())?;
+    return serde_json::from_str(&body).map_err(|e| e.to_string());
+}
+
+// Download an archive, or read it from the cache when it was downloaded before.
+// Returns its content and sha256, which must match the given one (if any).
+pub fn fetch_archive(url: &str, sha256: &str, offline: bool) -> Result<(Vec<u8>, String), String> {
+    if !sha256.is_empty() {
+        if let Some(data) = cache::archive_path(sha256).and_then(|path| fs::read(path).ok()) {
+            return Ok((data, sha256.to_string()));
+        }
+    }
+    if offline {
+        return Err(format!("{} isn't on the cache", url));
+    }
+
+    let data = download(url)?;
+    let hash = format!("{:x}", Sha256::digest(&data));
+    if !sha256.is_empty() && hash != sha256 {
+        return Err(format!("checksum of {} is {}, expected {}", url, hash, sha256));
+    }
+
+    if let Some(path) = cache::archive_path(&hash) {
+        // The cache is only a shortcut, so failing to write it isn't an error
+        let _ = fs::create_dir_all(path.parent().unwrap()).and_then(|_| fs::write(&path, &data));
+    }
+    return Ok((data, hash));
+}
+
+// Extract the addons/ folder of a zip or tar.gz archive into a folder, wherever it is
+// inside the archive (archives often wrap everything in a "repo-version/" folder)
+pub fn extract_addons(data: &[u8], target: &Path) -> Result<(), String> {
+    let found = if data.starts_with(b"PK") {
+        extract_zip(data, target)?
+    } else if data.starts_with(&[0x1f, 0x8b]) {
+        extract_tar_gz(data, target)?
+    } else {
+        return Err("archive isn't a zip or tar.gz file".to_string());
+    };
+
+    if !found {
+        return Err("archive has no addons folder".to_string());
+    }
+    return Ok(());
+}
+
+fn extract_zip(data: &[u8], target: &Path) -> Result<bool, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let mut found = false;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        let path = match entry.enclosed_name().and_then(|path| addons_path(&path)) {
+            Some(path) => target.join(path),
+            None => continue,
+        };
+        found = true;
+
+        if entry.is_dir() {
+            fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+            continue;
+        }
+
+        let mut content = vec![];
+        entry.read_to_end(&mut content).map_err(|e| e.to_string())?;
+        write_file(&path, &content, entry.unix_mode())?;
+    }
+
+    return Ok(found);
+}
+
+fn extract_tar_gz(data: &[u8], target: &Path) -> Result<bool, String> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(data));
+    let mut found = false;
+
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let entry_path = entry.path().map_err(|e| e.to_string())?.to_path_buf();
+        let path = match enclosed_path(&entry_path).and_then(|path| addons_path(&path)) {
+            Some(path) => target.join(path),
+            None => continue,
+        };
+        found = true;
+
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+            continue;
+        }
+        if !entry_type.is_file() {
+            continue;
+        }
+
+        let mut content = vec![];
+        entry.read_to_end(&mut content).map_err(|e| e.to_string())?;
+        write_file(&path, &content, entry.header().mode().ok())?;
+    }
+
+    return Ok(found);
+}
+
+fn write_file(path: &Path, content: &[u8], mode: Option<u32>) -> Result<(), String> {
+    fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::write(path, content))
+        .map_err(|e| e.to_string())?;
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        let res = fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o755));
+        res.map_err(|e| e.to_string())?;
+    }
+    return Ok(());
+}
+
+// Archive entry path, if it stays inside the folder it's extracted to
+fn enclosed_path(path: &Path) -> Option<PathBuf> {
+    let mut enclosed = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => enclosed.push(part),
+            Component::CurDir => (),
+            _ => return None,
+        }
+    }
+    return Some(enclosed);
+}
+
+// Path of an archive entry from its addons/ folder on, if it's inside one
+fn addons_path(path: &Path) -> Option<PathBuf> {
+    let components = path.components().collect::<Vec<Component>>();
+    let start = components
+        .iter()
+        .position(|c| c.as_os_str() == "addons")?;
+    return Some(components[start..].iter().collect());
+}
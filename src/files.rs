@@ -0,0 +1,379 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use super::utils;
+
+// Relative paths of every file (and symlink) inside a folder, recursively
+pub fn list_files(folder: &str) -> Vec<String> {
+    let mut files = vec![];
+
+    for entry in WalkDir::new(folder).min_depth(1).into_iter().flatten() {
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        if let Ok(relative) = entry.path().strip_prefix(folder) {
+            files.push(relative.to_string_lossy().to_string());
+        }
+    }
+
+    files.sort();
+    return files;
+}
+
+// Names of the folders directly inside a folder
+pub fn list_folders(folder: &str) -> io::Result<Vec<String>> {
+    let mut folders = vec![];
+
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            folders.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+
+    folders.sort();
+    return Ok(folders);
+}
+
+// Content of every file inside a folder, keyed by relative path.
+// Symlinks read as their target path, the way git stores them.
+pub fn read_files(folder: &str) -> BTreeMap<String, Vec<u8>> {
+    let mut contents = BTreeMap::new();
+
+    for file in list_files(folder) {
+        if let Some(content) = read_file(&format!("{}/{}", folder, file)) {
+            contents.insert(file, content);
+        }
+    }
+
+    return contents;
+}
+
+pub fn read_file(path: &str) -> Option<Vec<u8>> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+
+    if metadata.file_type().is_symlink() {
+        return Some(
+            fs::read_link(path)
+                .ok()?
+                .to_string_lossy()
+                .as_bytes()
+                .to_vec(),
+        );
+    }
+    if metadata.is_file() {
+        return fs::read(path).ok();
+    }
+    return None;
+}
+
+// Symlinks are hashed by their target path, not by what they point to
+pub fn hash_file(path: &str) -> Option<String> {
+    let content = read_file(path)?;
+    return Some(format!("{:x}", Sha256::digest(&content)));
+}
+
+// Copy a folder content into another, overwriting files that already exist
+pub fn copy_folder(source: &str, target: &str, verbose: bool) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+
+    for entry in WalkDir::new(source).min_depth(1) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(source).unwrap();
+        let target_path = Path::new(target).join(relative);
+        let target_metadata = fs::symlink_metadata(&target_path).ok();
+
+        if entry.file_type().is_dir() {
+            if let Some(metadata) = target_metadata {
+                if metadata.is_dir() {
+                    continue;
+                }
+                fs::remove_file(&target_path)?;
+            }
+            fs::create_dir(&target_path)?;
+            continue;
+        }
+
+        if let Some(metadata) = target_metadata {
+            if metadata.is_dir() {
+                fs::remove_dir_all(&target_path)?;
+            } else {
+                fs::remove_file(&target_path)?;
+            }
+        }
+
+        if entry.file_type().is_symlink() {
+            symlink(fs::read_link(entry.path())?, &target_path)?;
+        } else {
+            fs::copy(entry.path(), &target_path)?;
+        }
+
+        if verbose {
+            utils::log_info(&format!("Copied {}", target_path.display()));
+        }
+    }
+
+    return Ok(());
+}
+
+// Make `target` match `source`: copy the file (or symlink) over it, or remove it
+// when `source` doesn't exist. Returns whether `target` changed.
+pub fn sync_file(source: &str, target: &str) -> io::Result<bool> {
+    let source_metadata = fs::symlink_metadata(source).ok();
+    let target_metadata = fs::symlink_metadata(target).ok();
+
+    if let (Some(source_metadata), Some(target_metadata)) = (&source_metadata, &target_metadata) {
+        let same_type =
+            source_metadata.file_type().is_symlink() == target_metadata.file_type().is_symlink();
+        if source_metadata.is_dir() && target_metadata.is_dir() {
+            return Ok(false);
+        }
+        if same_type && !target_metadata.is_dir() && read_file(source) == read_file(target) {
+            return Ok(false);
+        }
+    }
+
+    match target_metadata {
+        Some(metadata) if metadata.is_dir() => fs::remove_dir_all(target)?,
+        Some(_) => fs::remove_file(target)?,
+        None if source_metadata.is_none() => return Ok(false),
+        None => (),
+    }
+
+    let source_metadata = match source_metadata {
+        Some(metadata) => metadata,
+        None => return Ok(true),
+    };
+
+    if source_metadata.is_dir() {
+        fs::create_dir_all(target)?;
+        return Ok(true);
+    }
+
+    if let Some(parent) = Path::new(target).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if source_metadata.file_type().is_symlink() {
+        symlink(fs::read_link(source)?, target)?;
+    } else {
+        fs::copy(source, target)?;
+    }
+    return Ok(true);
+}
+
+// Remove everything inside a folder, keeping the folder itself
+pub fn clear_folder(folder: &str) -> io::Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    return Ok(());
+}
+
+pub fn is_symlink(path: &str) -> bool {
+    return fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink());
+}
+
+// Make `target` a symlink to `source`, creating its parent folders
+pub fn link_folder(source: &str, target: &str) -> io::Result<()> {
+    if let Some(parent) = Path::new(target).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    return symlink(source, target);
+}
+
+// Remove a folder, or only the symlink when it is one
+pub fn remove_folder(folder: &str) -> io::Result<()> {
+    if fs::symlink_metadata(folder).is_err() {
+        return Ok(());
+    }
+    return fs::remove_dir_all(folder);
+}
+
+// Whether two folders have different files or file contents
+pub fn folders_differ(a: &str, b: &str) -> bool {
+    let files = list_files(a);
+    if files != list_files(b) {
+        return true;
+    }
+
+    return files.iter().any(|file| {
+        hash_file(&format!("{}/{}", a, file)) != hash_file(&format!("{}/{}", b, file))
+    });
+}
+
+// Remove the empty folders between a removed file and a root folder
+pub fn remove_empty_parents(file: &str, root: &str) {
+    let root = Path::new(root);
+    let mut dir = Path::new(file).parent();
+
+    while let Some(path) = dir {
+        if path == root || !path.starts_with(root) || fs::remove_dir(path).is_err() {
+            break;
+        }
+        dir = path.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    fn path(dir: &TempDir, relative: &str) -> String {
+        return dir.path().join(relative).to_string_lossy().to_string();
+    }
+
+    fn write(dir: &TempDir, relative: &str, content: &str) {
+        let file = dir.path().join(relative);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, content).unwrap();
+    }
+
+    // An addon with a space in a file name, a dotfile, a nested folder and a symlink
+    fn addon(dir: &TempDir, folder: &str) -> String {
+        write(
+            dir,
+            &format!("{}/plugin.gd", folder),
+            "extends EditorPlugin\n",
+        );
+        write(dir, &format!("{}/with space.gd", folder), "extends Node\n");
+        write(dir, &format!("{}/.gdignore", folder), "");
+        write(
+            dir,
+            &format!("{}/icons/nested/icon.svg", folder),
+            "<svg/>\n",
+        );
+        symlink("plugin.gd", dir.path().join(folder).join("link.gd")).unwrap();
+        return path(dir, folder);
+    }
+
+    #[test]
+    fn list_addon_files() {
+        let dir = TempDir::new().unwrap();
+        let folder = addon(&dir, "addons/foo");
+
+        assert_eq!(
+            list_files(&folder),
+            vec![
+                ".gdignore",
+                "icons/nested/icon.svg",
+                "link.gd",
+                "plugin.gd",
+                "with space.gd"
+            ]
+        );
+        assert_eq!(list_folders(&folder).unwrap(), vec!["icons"]);
+        assert!(list_files(&path(&dir, "missing")).is_empty());
+    }
+
+    #[test]
+    fn symlinks_read_as_their_target() {
+        let dir = TempDir::new().unwrap();
+        let folder = addon(&dir, "addons/foo");
+
+        let files = read_files(&folder);
+        assert_eq!(files["link.gd"], b"plugin.gd");
+        assert_eq!(files[".gdignore"], b"");
+        assert_ne!(
+            hash_file(&format!("{}/link.gd", folder)),
+            hash_file(&format!("{}/plugin.gd", folder))
+        );
+    }
+
+    #[test]
+    fn copy_addon_folder() {
+        let dir = TempDir::new().unwrap();
+        let source = addon(&dir, "source");
+        let target = path(&dir, "target");
+        write(&dir, "target/plugin.gd", "old\n");
+        // A file where the source has a folder, and the other way around
+        write(&dir, "target/icons", "not a folder\n");
+        write(&dir, "target/with space.gd/inner.gd", "not a file\n");
+
+        copy_folder(&source, &target, false).unwrap();
+        assert!(!folders_differ(&source, &target));
+        assert!(is_symlink(&format!("{}/link.gd", target)));
+
+        write(&dir, "target/icons/nested/icon.svg", "<svg></svg>\n");
+        assert!(folders_differ(&source, &target));
+    }
+
+    #[test]
+    fn sync_files() {
+        let dir = TempDir::new().unwrap();
+        let source = addon(&dir, "source");
+        let target = path(&dir, "target");
+
+        let sync = |file: &str| {
+            sync_file(
+                &format!("{}/{}", source, file),
+                &format!("{}/{}", target, file),
+            )
+        };
+        assert!(sync("with space.gd").unwrap());
+        assert!(!sync("with space.gd").unwrap());
+        assert!(sync("icons/nested/icon.svg").unwrap());
+        assert!(sync("link.gd").unwrap());
+        assert!(is_symlink(&format!("{}/link.gd", target)));
+
+        // Removed from the source, removed from the target
+        fs::remove_file(format!("{}/with space.gd", source)).unwrap();
+        assert!(sync("with space.gd").unwrap());
+        assert!(!Path::new(&format!("{}/with space.gd", target)).exists());
+        assert!(!sync("with space.gd").unwrap());
+    }
+
+    #[test]
+    fn clear_keeps_folder() {
+        let dir = TempDir::new().unwrap();
+        let folder = addon(&dir, "addons/foo");
+
+        clear_folder(&folder).unwrap();
+        assert!(Path::new(&folder).is_dir());
+        assert!(list_files(&folder).is_empty());
+    }
+
+    #[test]
+    fn remove_linked_folder() {
+        let dir = TempDir::new().unwrap();
+        let source = addon(&dir, ".glam.d/foo/addons/foo");
+        let target = path(&dir, "addons/foo");
+
+        link_folder("../.glam.d/foo/addons/foo", &target).unwrap();
+        assert!(is_symlink(&target));
+        assert_eq!(list_files(&format!("{}/", target)), list_files(&source));
+
+        // Only the link goes, not the files it points to
+        remove_folder(&target).unwrap();
+        assert!(fs::symlink_metadata(&target).is_err());
+        assert_eq!(list_files(&source).len(), 5);
+
+        remove_folder(&source).unwrap();
+        assert!(!Path::new(&source).exists());
+        remove_folder(&source).unwrap();
+    }
+
+    #[test]
+    fn remove_empty_folders() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "addons/foo/a/b/file.gd", "");
+        write(&dir, "addons/foo/a/other.gd", "");
+        let file = path(&dir, "addons/foo/a/b/file.gd");
+
+        fs::remove_file(&file).unwrap();
+        remove_empty_parents(&file, &path(&dir, "addons/foo"));
+        assert!(!Path::new(&path(&dir, "addons/foo/a/b")).exists());
+        assert!(Path::new(&path(&dir, "addons/foo/a")).exists());
+    }
+}
@@ -0,0 +1,544 @@
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use git2::{
+    BranchType, Cred, CredentialType, Direction, ErrorClass, ErrorCode, FetchOptions, IndexAddOption, ObjectType,
+    PushOptions, RemoteCallbacks, Repository, ResetType, StatusOptions, TreeWalkMode,
+    TreeWalkResult,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum GitError {
+    /// Path isn't a git repository
+    NotARepository(String),
+    /// Repository has no remote with that name
+    NoRemote(String),
+    /// Revision (commit, branch, tag) couldn't be found
+    RevisionNotFound(String),
+    /// Local branch diverged from its upstream
+    NotFastForward(String),
+    /// Nothing changed since the last commit
+    NothingToCommit,
+    /// HEAD isn't on a branch
+    DetachedHead,
+    /// Remote refused to update a branch
+    PushRejected(String, String),
+    /// Remote refused every credential glam could find
+    AuthenticationFailed(String),
+    Git(git2::Error),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitError::NotARepository(path) => write!(f, "{} is not a git repository", path),
+            GitError::NoRemote(remote) => write!(f, "remote {} doesn't exist", remote),
+            GitError::RevisionNotFound(rev) => write!(f, "revision {} not found", rev),
+            GitError::NotFastForward(branch) => {
+                write!(f, "branch {} can't be fast-forwarded to its upstream", branch)
+            }
+            GitError::NothingToCommit => write!(f, "nothing to commit"),
+            GitError::DetachedHead => write!(f, "HEAD isn't on a branch"),
+            GitError::PushRejected(branch, reason) => {
+                write!(f, "push of branch {} was rejected: {}", branch, reason)
+            }
+            GitError::AuthenticationFailed(url) => write!(f, "couldn't authenticate to {}", url),
+            GitError::Git(e) => write!(f, "{}", e.message()),
+        }
+    }
+}
+
+impl From<git2::Error> for GitError {
+    fn from(e: git2::Error) -> Self {
+        // Raised by the credentials callback once it runs out of credentials
+        if e.code() == ErrorCode::Auth && e.class() == ErrorClass::Callback {
+            return GitError::AuthenticationFailed(e.message().to_string());
+        }
+        return GitError::Git(e);
+    }
+}
+
+/// Git operations used to manage package repositories
+pub trait GitBackend: Send + Sync {
+    fn init(&self, path: &Path) -> Result<(), GitError>;
+    /// Clone a repository, checking out a branch instead of the remote default one
+    fn clone(&self, url: &str, path: &Path, branch: Option<&str>) -> Result<(), GitError>;
+    /// Fetch all branches and tags from origin
+    fn fetch(&self, path: &Path) -> Result<(), GitError>;
+    /// Fetch all branches and tags from another repository, as if it were origin
+    fn fetch_from(&self, path: &Path, url: &str) -> Result<(), GitError>;
+    /// Fast-forward the current branch to its upstream
+    fn fast_forward(&self, path: &Path) -> Result<(), GitError>;
+    /// Fetch origin and fast-forward the current branch to its upstream
+    fn pull(&self, path: &Path) -> Result<(), GitError> {
+        self.fetch(path)?;
+        return self.fast_forward(path);
+    }
+    /// Create or update a bare mirror of a repository
+    fn update_mirror(&self, url: &str, path: &Path) -> Result<(), GitError>;
+    /// Commit hash of a revision
+    fn rev_parse(&self, path: &Path, rev: &str) -> Result<String, GitError>;
+    fn reset_hard(&self, path: &Path, rev: &str) -> Result<(), GitError>;
+    /// Switch to a local branch, creating it from origin if needed
+    fn checkout(&self, path: &Path, branch: &str) -> Result<(), GitError>;
+    /// Whether a commit is an ancestor of (or the same as) another
+    fn is_ancestor(&self, path: &Path, ancestor: &str, descendant: &str) -> Result<bool, GitError>;
+    fn remote_url(&self, path: &Path, remote: &str) -> Result<String, GitError>;
+    fn set_remote_url(&self, path: &Path, remote: &str, url: &str) -> Result<(), GitError>;
+    /// Whether the working tree has uncommitted changes, untracked files included
+    fn is_dirty(&self, path: &Path) -> Result<bool, GitError>;
+    /// Commits of the current branch missing on its upstream, if it has one
+    fn unpushed_commits(&self, path: &Path) -> Result<Option<usize>, GitError>;
+    /// Whether HEAD can be reached from a reference under some prefixes (like refs/remotes/),
+    /// which is also true when there's no commit yet
+    fn head_reachable(&self, path: &Path, prefixes: &[&str]) -> Result<bool, GitError>;
+    /// Stage every change inside some folders and commit it, returning the commit hash
+    fn commit(&self, path: &Path, folders: &[String], message: &str) -> Result<String, GitError>;
+    /// Create a branch at HEAD and switch to it
+    fn create_branch(&self, path: &Path, branch: &str) -> Result<(), GitError>;
+    /// Push the current branch to origin and track it, returning the branch name
+    fn push(&self, path: &Path) -> Result<String, GitError>;
+    /// Commit the files of a folder as a commit without parents, author or date, so the
+    /// same files give the same commit everywhere, and point a reference to it. The
+    /// working tree and the index are left as they are.
+    fn snapshot(&self, path: &Path, folder: &Path, reference: &str, message: &str) -> Result<String, GitError>;
+    /// Content of a file at a revision, if it exists there
+    fn read_file(&self, path: &Path, rev: &str, file: &str) -> Result<Option<Vec<u8>>, GitError>;
+    /// Content of the files inside a folder at a revision, keyed by relative path
+    fn read_files(
+        &self,
+        path: &Path,
+        rev: &str,
+        folder: &str,
+    ) -> Result<BTreeMap<String, Vec<u8>>, GitError>;
+    /// Names of all tags (fetched from origin by fetch and pull)
+    fn tags(&self, path: &Path) -> Result<Vec<String>, GitError>;
+}
+
+/// In-process backend built on libgit2
+pub struct Git2Backend;
+
+pub fn backend() -> Box<dyn GitBackend> {
+    return Box::new(Git2Backend);
+}
+
+fn open(path: &Path) -> Result<Repository, GitError> {
+    return Repository::open(path)
+        .map_err(|_| GitError::NotARepository(path.display().to_string()));
+}
+
+const FETCH_REFSPECS: [&str; 2] = ["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"];
+const MIRROR_REFSPECS: [&str; 2] = ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+fn fetch_options<'a>() -> FetchOptions<'a> {
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(remote_callbacks());
+    return options;
+}
+
+const SSH_KEY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+// Private keys on ~/.ssh, with their public key when it's next to them
+fn ssh_key_files() -> Vec<(PathBuf, Option<PathBuf>)> {
+    let home = match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home),
+        None => return vec![],
+    };
+
+    return SSH_KEY_FILES
+        .iter()
+        .map(|name| home.join(".ssh").join(name))
+        .filter(|key| key.is_file())
+        .map(|key| {
+            let public = key.with_extension("pub");
+            (key, Some(public).filter(|public| public.is_file()))
+        })
+        .collect();
+}
+
+// libgit2 calls back for as long as the remote refuses the credentials, so each
+// one is tried once (the SSH agent, then ~/.ssh keys, then the credential helper)
+// before giving up
+fn remote_callbacks<'a>() -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut ssh_attempts = 0;
+    let mut tried_helper = false;
+    let mut tried_default = false;
+
+    callbacks.credentials(move |url, username, allowed| {
+        let user = username.unwrap_or("git");
+
+        // ssh:// URLs without a user ask for one before any key
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(user);
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            ssh_attempts += 1;
+            if ssh_attempts == 1 {
+                return Cred::ssh_key_from_agent(user);
+            }
+            if let Some((key, public)) = ssh_key_files().get(ssh_attempts - 2) {
+                return Cred::ssh_key(user, public.as_deref(), key, None);
+            }
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_helper {
+            tried_helper = true;
+            let config = git2::Config::open_default()?;
+            if let Ok(cred) = Cred::credential_helper(&config, url, username) {
+                return Ok(cred);
+            }
+        }
+
+        if allowed.contains(CredentialType::DEFAULT) && !tried_default {
+            tried_default = true;
+            return Cred::default();
+        }
+
+        return Err(git2::Error::new(ErrorCode::Auth, ErrorClass::Callback, url));
+    });
+    return callbacks;
+}
+
+fn find_commit<'a>(repo: &'a Repository, rev: &str) -> Result<git2::Commit<'a>, GitError> {
+    let object = repo
+        .revparse_single(rev)
+        .map_err(|_| GitError::RevisionNotFound(rev.to_string()))?;
+    return Ok(object.peel_to_commit()?);
+}
+
+impl GitBackend for Git2Backend {
+    fn init(&self, path: &Path) -> Result<(), GitError> {
+        Repository::init(path)?;
+        return Ok(());
+    }
+
+    fn clone(&self, url: &str, path: &Path, branch: Option<&str>) -> Result<(), GitError> {
+        let mut builder = RepoBuilder::new();
+        builder.fetch_options(fetch_options());
+        if let Some(branch) = branch {
+            builder.branch(branch);
+        }
+        builder.clone(url, path)?;
+        return Ok(());
+    }
+
+    fn fetch(&self, path: &Path) -> Result<(), GitError> {
+        let repo = open(path)?;
+        let mut remote = repo
+            .find_remote("origin")
+            .map_err(|_| GitError::NoRemote("origin".to_string()))?;
+        remote.fetch(&FETCH_REFSPECS, Some(&mut fetch_options()), None)?;
+        return Ok(());
+    }
+
+    fn fetch_from(&self, path: &Path, url: &str) -> Result<(), GitError> {
+        let repo = open(path)?;
+        let mut remote = repo.remote_anonymous(url)?;
+        remote.fetch(&FETCH_REFSPECS, Some(&mut fetch_options()), None)?;
+        return Ok(());
+    }
+
+    fn fast_forward(&self, path: &Path) -> Result<(), GitError> {
+        let repo = open(path)?;
+        let head = repo.head()?;
+        if !head.is_branch() {
+            // Detached HEAD, nothing to fast-forward
+            return Ok(());
+        }
+
+        let branch_name = head.shorthand().unwrap_or("HEAD").to_string();
+        let branch = git2::Branch::wrap(head);
+        let upstream = match branch.upstream() {
+            Ok(upstream) => upstream,
+            Err(_) => return Ok(()),
+        };
+        let upstream_commit = repo.reference_to_annotated_commit(upstream.get())?;
+
+        let (analysis, _) = repo.merge_analysis(&[&upstream_commit])?;
+        if analysis.is_up_to_date() {
+            return Ok(());
+        }
+        if !analysis.is_fast_forward() {
+            return Err(GitError::NotFastForward(branch_name));
+        }
+
+        let mut reference = repo.find_reference(&format!("refs/heads/{}", branch_name))?;
+        // Uncommitted changes are kept, unless the new commits touch the same files
+        let target = repo.find_commit(upstream_commit.id())?;
+        repo.checkout_tree(target.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        reference.set_target(upstream_commit.id(), "glam: fast-forward")?;
+        repo.set_head(&format!("refs/heads/{}", branch_name))?;
+        return Ok(());
+    }
+
+    fn update_mirror(&self, url: &str, path: &Path) -> Result<(), GitError> {
+        let repo = match Repository::open_bare(path) {
+            Ok(repo) => repo,
+            Err(_) => {
+                let repo = Repository::init_bare(path)?;
+                repo.remote("origin", url)?;
+                repo
+            }
+        };
+
+        let mut remote = repo.find_remote("origin")?;
+        remote.fetch(&MIRROR_REFSPECS, Some(&mut fetch_options()), None)?;
+
+        // Point HEAD to the remote default branch, so clones of the mirror check it out
+        let default_branch = {
+            let connection = remote.connect_auth(Direction::Fetch, Some(remote_callbacks()), None)?;
+            connection.default_branch()?.as_str().map(|b| b.to_string())
+        };
+        if let Some(branch) = default_branch {
+            repo.set_head(&branch)?;
+        }
+        return Ok(());
+    }
+
+    fn rev_parse(&self, path: &Path, rev: &str) -> Result<String, GitError> {
+        let repo = open(path)?;
+        let commit = find_commit(&repo, rev)?;
+        return Ok(commit.id().to_string());
+    }
+
+    fn reset_hard(&self, path: &Path, rev: &str) -> Result<(), GitError> {
+        let repo = open(path)?;
+        let commit = find_commit(&repo, rev)?;
+        repo.reset(commit.as_object(), ResetType::Hard, None)?;
+        return Ok(());
+    }
+
+    fn checkout(&self, path: &Path, branch: &str) -> Result<(), GitError> {
+        let repo = open(path)?;
+        let reference = format!("refs/heads/{}", branch);
+        if repo.head().is_ok_and(|head| head.name() == Some(reference.as_str())) {
+            return Ok(());
+        }
+
+        if repo.find_branch(branch, BranchType::Local).is_err() {
+            let upstream_name = format!("origin/{}", branch);
+            let upstream = repo
+                .find_branch(&upstream_name, BranchType::Remote)
+                .map_err(|_| GitError::RevisionNotFound(upstream_name.to_string()))?;
+            let commit = upstream.get().peel_to_commit()?;
+            let mut local = repo.branch(branch, &commit, false)?;
+            local.set_upstream(Some(&upstream_name))?;
+        }
+
+        // Fails instead of overwriting uncommitted changes to files the branches don't share
+        let target = repo.find_reference(&reference)?.peel_to_commit()?;
+        repo.checkout_tree(target.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        repo.set_head(&reference)?;
+        return Ok(());
+    }
+
+    fn is_ancestor(&self, path: &Path, ancestor: &str, descendant: &str) -> Result<bool, GitError> {
+        let repo = open(path)?;
+        let ancestor = find_commit(&repo, ancestor)?.id();
+        let descendant = find_commit(&repo, descendant)?.id();
+        return Ok(ancestor == descendant || repo.graph_descendant_of(descendant, ancestor)?);
+    }
+
+    fn remote_url(&self, path: &Path, remote: &str) -> Result<String, GitError> {
+        let repo = open(path)?;
+        let remote = repo
+            .find_remote(remote)
+            .map_err(|_| GitError::NoRemote(remote.to_string()))?;
+        return match remote.url() {
+            Some(url) => Ok(url.to_string()),
+            None => Err(GitError::NoRemote(remote.name().unwrap_or("").to_string())),
+        };
+    }
+
+    fn tags(&self, path: &Path) -> Result<Vec<String>, GitError> {
+        let repo = open(path)?;
+        let names = repo.tag_names(None)?;
+        return Ok(names.iter().flatten().map(|name| name.to_string()).collect());
+    }
+
+    fn set_remote_url(&self, path: &Path, remote: &str, url: &str) -> Result<(), GitError> {
+        let repo = open(path)?;
+        repo.remote_set_url(remote, url)?;
+        return Ok(());
+    }
+
+    fn is_dirty(&self, path: &Path) -> Result<bool, GitError> {
+        let repo = open(path)?;
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).recurse_untracked_dirs(true);
+        return Ok(!repo.statuses(Some(&mut options))?.is_empty());
+    }
+
+    fn unpushed_commits(&self, path: &Path) -> Result<Option<usize>, GitError> {
+        let repo = open(path)?;
+        let head = match repo.head() {
+            Ok(head) if head.is_branch() => head,
+            _ => return Ok(None),
+        };
+        let local = match head.target() {
+            Some(local) => local,
+            None => return Ok(None),
+        };
+
+        let upstream = match git2::Branch::wrap(head).upstream() {
+            Ok(upstream) => upstream,
+            Err(_) => return Ok(None),
+        };
+        let upstream = upstream.get().peel_to_commit()?.id();
+
+        let (ahead, _) = repo.graph_ahead_behind(local, upstream)?;
+        return Ok(Some(ahead));
+    }
+
+    fn head_reachable(&self, path: &Path, prefixes: &[&str]) -> Result<bool, GitError> {
+        let repo = open(path)?;
+        let head = match repo.head().ok().and_then(|head| head.target()) {
+            Some(head) => head,
+            None => return Ok(true),
+        };
+
+        for reference in repo.references()?.flatten() {
+            let name = reference.name().unwrap_or("");
+            if !prefixes.iter().any(|prefix| name.starts_with(prefix)) {
+                continue;
+            }
+            let target = match reference.peel_to_commit() {
+                Ok(commit) => commit.id(),
+                Err(_) => continue,
+            };
+            if target == head || repo.graph_descendant_of(target, head)? {
+                return Ok(true);
+            }
+        }
+        return Ok(false);
+    }
+
+    fn read_files(
+        &self,
+        path: &Path,
+        rev: &str,
+        folder: &str,
+    ) -> Result<BTreeMap<String, Vec<u8>>, GitError> {
+        let repo = open(path)?;
+        let tree = find_commit(&repo, rev)?.tree()?;
+        let mut contents = BTreeMap::new();
+
+        let folder_tree = match tree.get_path(Path::new(folder)) {
+            Ok(entry) => entry.to_object(&repo)?.peel_to_tree()?,
+            Err(_) => return Ok(contents),
+        };
+
+        folder_tree.walk(TreeWalkMode::PreOrder, |parent, entry| {
+            if entry.kind() != Some(ObjectType::Blob) {
+                return TreeWalkResult::Ok;
+            }
+            if let Ok(blob) = repo.find_blob(entry.id()) {
+                let name = entry.name().unwrap_or_default();
+                contents.insert(format!("{}{}", parent, name), blob.content().to_vec());
+            }
+            return TreeWalkResult::Ok;
+        })?;
+
+        return Ok(contents);
+    }
+
+    fn commit(&self, path: &Path, folders: &[String], message: &str) -> Result<String, GitError> {
+        let repo = open(path)?;
+
+        let mut index = repo.index()?;
+        index.add_all(folders.iter(), IndexAddOption::DEFAULT, None)?;
+        index.update_all(folders.iter(), None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+
+        // HEAD is unborn on repositories without commits yet
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        if parent.as_ref().is_some_and(|parent| parent.tree_id() == tree.id()) {
+            return Err(GitError::NothingToCommit);
+        }
+
+        let signature = repo.signature()?;
+        let parents = parent.iter().collect::<Vec<&git2::Commit>>();
+        let id = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+        return Ok(id.to_string());
+    }
+
+    fn create_branch(&self, path: &Path, branch: &str) -> Result<(), GitError> {
+        let repo = open(path)?;
+        let commit = repo.head()?.peel_to_commit()?;
+        repo.branch(branch, &commit, false)?;
+        repo.set_head(&format!("refs/heads/{}", branch))?;
+        return Ok(());
+    }
+
+    fn push(&self, path: &Path) -> Result<String, GitError> {
+        let repo = open(path)?;
+        let head = repo.head()?;
+        if !head.is_branch() {
+            return Err(GitError::DetachedHead);
+        }
+        let branch = head.shorthand().unwrap_or("HEAD").to_string();
+
+        let mut remote = repo
+            .find_remote("origin")
+            .map_err(|_| GitError::NoRemote("origin".to_string()))?;
+
+        let rejected = RefCell::new(None);
+        let mut callbacks = remote_callbacks();
+        callbacks.push_update_reference(|_, status| {
+            if let Some(status) = status {
+                *rejected.borrow_mut() = Some(status.to_string());
+            }
+            return Ok(());
+        });
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+
+        let refspec = format!("refs/heads/{0}:refs/heads/{0}", branch);
+        remote.push(&[refspec], Some(&mut options))?;
+        drop(options);
+
+        if let Some(reason) = rejected.into_inner() {
+            return Err(GitError::PushRejected(branch, reason));
+        }
+
+        let mut local = repo.find_branch(&branch, BranchType::Local)?;
+        local.set_upstream(Some(&format!("origin/{}", branch)))?;
+        return Ok(branch);
+    }
+
+    fn read_file(&self, path: &Path, rev: &str, file: &str) -> Result<Option<Vec<u8>>, GitError> {
+        let repo = open(path)?;
+        let tree = find_commit(&repo, rev)?.tree()?;
+
+        let entry = match tree.get_path(Path::new(file)) {
+            Ok(entry) => entry,
+            Err(_) => return Ok(None),
+        };
+        return match entry.to_object(&repo)?.into_blob() {
+            Ok(blob) => Ok(Some(blob.content().to_vec())),
+            Err(_) => Ok(None),
+        };
+    }
+
+    fn snapshot(&self, path: &Path, folder: &Path, reference: &str, message: &str) -> Result<String, GitError> {
+        let repo = open(path)?;
+        repo.set_workdir(folder, false)?;
+
+        // Only written as a tree, the index file isn't updated
+        let mut index = repo.index()?;
+        index.clear()?;
+        index.add_all(["*"].iter(), IndexAddOption::FORCE, None)?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+
+        let signature = git2::Signature::new("glam", "glam@localhost", &git2::Time::new(0, 0))?;
+        let id = repo.commit(None, &signature, &signature, message, &tree, &[])?;
+        repo.reference(reference, id, true, message)?;
+        return Ok(id.to_string());
+    }
+}
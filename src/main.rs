@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use clap::{Parser, Subcommand};
 
 mod commands;
@@ -6,11 +8,16 @@ mod commands;
 struct Cli {
     #[clap(subcommand)]
     command: Commands,
-    /// Verbose (output subshell commands)
+    /// Verbose (output file operations)
     #[clap(short, long, takes_value = false)]
     verbose: bool,
+    /// Never prompt: take defaults, and fail when a flag is missing
+    #[clap(short = 'y', long = "yes", alias = "non-interactive", global = true, takes_value = false)]
+    non_interactive: bool,
 }
 
+// Commands are parsed once, so a large variant costs nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize Godot project for GLAM
@@ -19,25 +26,173 @@ enum Commands {
     /// Add new repository
     Add {
         /// Package project git
-        git_repo: String,
+        #[clap(required_unless_present_any = &["asset", "path"])]
+        git_repo: Option<String>,
+        /// Name of the addon
+        #[clap(long)]
+        name: Option<String>,
+        /// Commit hash of the repository
+        #[clap(long, conflicts_with_all = &["branch", "tag", "version"])]
+        commit: Option<String>,
+        /// Branch to follow (e.g. godot-4)
+        #[clap(short, long, conflicts_with = "tag")]
+        branch: Option<String>,
+        /// Tag to install
+        #[clap(short, long, conflicts_with = "version")]
+        tag: Option<String>,
+        /// Semver requirement resolved against the repository tags (e.g. ^2.1)
+        #[clap(long)]
+        version: Option<String>,
+        /// Addon folders to install, when the repository has several
+        #[clap(long, multiple_values = true, use_value_delimiter = true)]
+        links: Vec<String>,
+        /// Godot Asset Library id or name to download instead of a git repository
+        #[clap(long, conflicts_with_all = &["git-repo", "commit", "branch", "tag", "version"])]
+        asset: Option<String>,
+        /// Download the release asset matching this name (e.g. "my-addon-*.zip") instead of
+        /// cloning; the latest release, or the one of --tag
+        #[clap(long, conflicts_with_all = &["asset", "commit", "branch", "version"])]
+        release: Option<String>,
+        /// Forge hosting the releases, guessed from the repository url by default
+        #[clap(long, requires = "release", possible_values = commands::FORGES)]
+        forge: Option<String>,
+        /// API url of the forge, for self-hosted ones
+        #[clap(long, requires = "release")]
+        api_url: Option<String>,
+        /// Local folder with the addon, installed from it without a .glam.d repository
+        #[clap(long, conflicts_with_all = &["git-repo", "commit", "branch", "tag", "version", "asset", "release"])]
+        path: Option<String>,
+        /// Symlink the addon folders to the package instead of copying them
+        #[clap(long)]
+        link: bool,
     },
 
     /// Create a repository from an existing addon
     Create {
+        /// Addon folder to create the repository from
+        #[clap(long)]
+        addon: Option<String>,
+        /// Name of the repository
+        #[clap(long)]
+        name: Option<String>,
+        /// Create the repository on this folder (or use the one there) instead of .glam.d
+        #[clap(long)]
+        path: Option<String>,
+        /// Replace the addon folder with a symlink to the repository
+        #[clap(long)]
+        link: bool,
     },
 
-    /// Update a repository
-    Update {},
+    /// Update repositories
+    Update {
+        /// Packages to update
+        names: Vec<String>,
+        /// Update all packages
+        #[clap(short, long, takes_value = false, conflicts_with = "names")]
+        all: bool,
+        /// Allow updating past the package version requirement major release
+        #[clap(long, takes_value = false)]
+        major: bool,
+        /// Show what would change on addons without installing anything
+        #[clap(long, takes_value = false)]
+        dry_run: bool,
+        /// What to do with addon files changed locally
+        #[clap(long, possible_values = commands::LOCAL_CHANGES_ACTIONS)]
+        local_changes: Option<String>,
+    },
 
     /// Install all addons on glam file
-    Install {},
+    Install {
+        /// Number of repositories cloned or fetched at the same time
+        #[clap(short, long, default_value = "4")]
+        jobs: usize,
+        /// Install from .glam.d and the cache only, without network access
+        #[clap(long, takes_value = false)]
+        offline: bool,
+        /// What to do with addon files changed locally
+        #[clap(long, possible_values = commands::LOCAL_CHANGES_ACTIONS)]
+        local_changes: Option<String>,
+    },
 
     /// Apply changes to a repository
-    Apply {},
+    Apply {
+        /// Package to apply changes to
+        #[clap(long)]
+        package: Option<String>,
+        /// Show what would change on the repository without copying anything
+        #[clap(long, takes_value = false)]
+        dry_run: bool,
+        /// Commit the applied changes on the repository
+        #[clap(long, takes_value = false)]
+        commit: bool,
+        /// Message of the commit
+        #[clap(short, long, requires = "commit")]
+        message: Option<String>,
+        /// Push the commit to origin
+        #[clap(long, takes_value = false, requires = "commit")]
+        push: bool,
+        /// Commit to a new branch instead of the current one
+        #[clap(short, long, requires = "commit")]
+        branch: Option<String>,
+    },
+
+    /// Keep applying addon changes to their repositories as they happen
+    Watch {
+        /// Name of the package, all of them by default
+        name: Option<String>,
+    },
+
+    /// Show what apply and install would change on a package
+    Diff {
+        /// Name of the package
+        name: String,
+    },
+
+    /// Save changes made to a package addons as a patch, reapplied on install
+    Patch {
+        /// Name of the package
+        name: String,
+    },
+
+    /// Show differences between addons, their repositories and the lock
+    Status {},
+
+    /// Show the installed packages and their dependencies
+    Tree {
+        /// Print the tree as JSON
+        #[clap(long, takes_value = false, conflicts_with = "dot")]
+        json: bool,
+        /// Print the dependency graph in Graphviz DOT format
+        #[clap(long, takes_value = false)]
+        dot: bool,
+    },
+
+    /// Show which packages on .glam brought a package in
+    Why {
+        /// Name of the package
+        name: String,
+        /// Print the dependency paths as JSON
+        #[clap(long, takes_value = false)]
+        json: bool,
+    },
+
+    /// Remove a repository and its addons
+    Remove {
+        /// Name of the package to remove
+        name: String,
+        /// Also delete the package repository on .glam.d
+        #[clap(long, takes_value = false)]
+        purge: bool,
+        /// Remove even if the addons have changes not applied to the repository, or the
+        /// purged repository has uncommitted or unpushed changes
+        #[clap(short, long, takes_value = false)]
+        force: bool,
+    },
 }
 
 fn main() {
     let cli = Cli::parse();
+    let interactive = !cli.non_interactive;
 
     match &cli.command {
         Commands::Init {} => {
@@ -46,38 +201,168 @@ fn main() {
             commands::initialize(&root);
         }
 
-        Commands::Add { git_repo } => {
+        Commands::Add {
+            git_repo,
+            name,
+            commit,
+            branch,
+            tag,
+            version,
+            links,
+            asset,
+            release,
+            forge,
+            api_url,
+            path,
+            link,
+        } => {
             let root = commands::search_project_root();
             if commands::check_initialization(&root) {
-                commands::add_repository(&root, git_repo, cli.verbose);
+                let options = commands::PackageOptions {
+                    name: name.clone(),
+                    commit: commit.clone(),
+                    branch: branch.clone(),
+                    tag: tag.clone(),
+                    version: version.clone(),
+                    links: links.clone(),
+                    asset: asset.clone(),
+                    release: release.clone(),
+                    forge: forge.clone(),
+                    api_url: api_url.clone(),
+                    path: path.clone(),
+                    link: *link,
+                };
+                let git_repo = git_repo.as_deref().unwrap_or_default();
+                commands::add_repository(&root, git_repo, &options, interactive, cli.verbose);
             }
         }
 
-        Commands::Create { } => {
+        Commands::Create { addon, name, path, link } => {
             let root = commands::search_project_root();
             if commands::check_initialization(&root) {
-                commands::create_addon(&root, cli.verbose);
+                commands::create_addon(
+                    &root,
+                    addon.as_deref(),
+                    name.as_deref(),
+                    path.as_deref(),
+                    *link,
+                    interactive,
+                    cli.verbose,
+                );
             }
         }
         
-        Commands::Update {} => {
+        Commands::Update {
+            names,
+            all,
+            major,
+            dry_run,
+            local_changes,
+        } => {
+            let root = commands::search_project_root();
+            if commands::check_initialization(&root) {
+                let options = commands::UpdateOptions {
+                    all: *all,
+                    major: *major,
+                    dry_run: *dry_run,
+                    local_changes: local_changes.clone(),
+                };
+                commands::update_repository(&root, names, &options, interactive, cli.verbose);
+            }
+        }
+
+        Commands::Install {
+            jobs,
+            offline,
+            local_changes,
+        } => {
+            let root = commands::search_project_root();
+            if commands::check_initialization(&root) {
+                commands::install_repositories(
+                    &root,
+                    *jobs,
+                    *offline,
+                    local_changes.as_deref(),
+                    interactive,
+                    cli.verbose,
+                );
+            }
+        }
+
+        Commands::Apply {
+            package,
+            dry_run,
+            commit,
+            message,
+            push,
+            branch,
+        } => {
             let root = commands::search_project_root();
             if commands::check_initialization(&root) {
-                commands::update_repository(&root, cli.verbose);
+                let options = commands::ApplyOptions {
+                    package: package.clone(),
+                    dry_run: *dry_run,
+                    commit: *commit,
+                    message: message.clone(),
+                    push: *push,
+                    branch: branch.clone(),
+                };
+                commands::apply_changes(&root, &options, interactive, cli.verbose);
             }
         }
 
-        Commands::Install {} => {
+        Commands::Watch { name } => {
+            let root = commands::search_project_root();
+            if commands::check_initialization(&root) {
+                commands::watch_packages(&root, name.as_deref(), cli.verbose);
+            }
+        }
+
+        Commands::Diff { name } => {
+            let root = commands::search_project_root();
+            if commands::check_initialization(&root) {
+                commands::show_diff(&root, name);
+            }
+        }
+
+        Commands::Patch { name } => {
+            let root = commands::search_project_root();
+            if commands::check_initialization(&root) {
+                commands::create_patch(&root, name);
+            }
+        }
+
+        Commands::Status {} => {
+            let root = commands::search_project_root();
+            if commands::check_initialization(&root) {
+                commands::show_status(&root);
+            }
+        }
+
+        Commands::Tree { json, dot } => {
+            if *json || *dot {
+                commands::log_to_stderr();
+            }
+            let root = commands::search_project_root();
+            if commands::check_initialization(&root) {
+                commands::show_tree(&root, *json, *dot);
+            }
+        }
+
+        Commands::Why { name, json } => {
+            if *json {
+                commands::log_to_stderr();
+            }
             let root = commands::search_project_root();
             if commands::check_initialization(&root) {
-                commands::install_repositories(&root, cli.verbose);
+                commands::show_why(&root, name, *json);
             }
         }
 
-        Commands::Apply {} => {
+        Commands::Remove { name, purge, force } => {
             let root = commands::search_project_root();
             if commands::check_initialization(&root) {
-                commands::apply_changes(&root, cli.verbose);
+                commands::remove_repository(&root, name, *purge, *force);
             }
         }
     }
@@ -0,0 +1,97 @@
+use inquire::{InquireError, MultiSelect, Select, Text};
+use std::process::exit;
+
+use super::utils;
+
+// Prompts answered by a command line flag when given. Without a terminal
+// (or with --yes) defaults are taken, and prompts without one fail.
+
+pub fn text(message: &str, default: &str, value: Option<&str>, interactive: bool) -> String {
+    if let Some(value) = value {
+        return value.to_string();
+    }
+    if !interactive {
+        return default.to_string();
+    }
+
+    let res = Text::new(message)
+        .with_default(default)
+        .with_placeholder(default)
+        .prompt();
+    return answer(res, message);
+}
+
+pub fn select(
+    message: &str,
+    options: Vec<String>,
+    value: Option<&str>,
+    flag: &str,
+    interactive: bool,
+) -> String {
+    if let Some(value) = value {
+        if !options.iter().any(|option| option == value) {
+            utils::log_error(&format!("{} isn't one of: {}", value, options.join(", ")));
+            exit(1);
+        }
+        return value.to_string();
+    }
+    if !interactive {
+        missing_flag(message, flag);
+    }
+
+    let res = Select::new(message, options).prompt();
+    return answer(res, message);
+}
+
+pub fn multi_select(
+    message: &str,
+    options: Vec<String>,
+    values: &[String],
+    flag: &str,
+    interactive: bool,
+) -> Vec<String> {
+    if !values.is_empty() {
+        for value in values {
+            if !options.contains(value) {
+                utils::log_error(&format!("{} isn't one of: {}", value, options.join(", ")));
+                exit(1);
+            }
+        }
+        return values.to_vec();
+    }
+    if !interactive {
+        missing_flag(message, flag);
+    }
+
+    let res = MultiSelect::new(message, options).prompt();
+    return answer(res, message);
+}
+
+fn missing_flag(message: &str, flag: &str) -> ! {
+    utils::log_error(&format!(
+        "Can't prompt \"{}\" in non-interactive mode, use {}",
+        message, flag
+    ));
+    exit(1);
+}
+
+fn answer<T>(res: Result<T, InquireError>, message: &str) -> T {
+    match res {
+        Ok(value) => return value,
+        Err(InquireError::NotTTY) => {
+            utils::log_error(&format!(
+                "Can't prompt \"{}\" without a terminal, use --yes and the command flags",
+                message
+            ));
+            exit(1);
+        }
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            utils::log_info("Canceled");
+            exit(1);
+        }
+        Err(e) => {
+            utils::log_error(&format!("{}", e));
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use serde::Deserialize;
+
+use super::download;
+
+pub const FORGES: &[&str] = &["github", "gitlab"];
+
+/// A file attached to a forge release
+pub struct ReleaseAsset {
+    pub tag: String,
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct GitHubRelease {
+    tag_name: String,
+    assets: Vec<GitHubAsset>,
+}
+
+#[derive(Deserialize)]
+struct GitHubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    assets: GitLabAssets,
+}
+
+#[derive(Deserialize)]
+struct GitLabAssets {
+    #[serde(default)]
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Deserialize)]
+struct GitLabLink {
+    name: String,
+    url: String,
+    #[serde(default)]
+    direct_asset_url: Option<String>,
+}
+
+// Forge hosting a repository, guessed from its url
+pub fn guess_forge(git_repo: &str) -> String {
+    if git_repo.contains("gitlab") {
+        return "gitlab".to_string();
+    }
+    return "github".to_string();
+}
+
+// Public API of a forge
+pub fn default_api_url(forge: &str, git_repo: &str) -> String {
+    if forge == "gitlab" {
+        return format!(
+            "{}/api/v4",
+            repository_host(git_repo).unwrap_or("https://gitlab.com".to_string())
+        );
+    }
+    return "https://api.github.com".to_string();
+}
+
+// Find the asset of a release (the latest one, without a tag) matching a name pattern
+pub fn find_asset(
+    forge: &str,
+    api_url: &str,
+    git_repo: &str,
+    tag: Option<&str>,
+    pattern: &str,
+) -> Result<ReleaseAsset, String> {
+    let project =
+        repository_path(git_repo).ok_or(format!("{} isn't a repository url", git_repo))?;
+    let api_url = api_url.trim_end_matches('/');
+
+    let (tag_name, assets) = match forge {
+        "github" => {
+            let release = match tag {
+                Some(tag) => format!("tags/{}", tag),
+                None => "latest".to_string(),
+            };
+            let url = format!("{}/repos/{}/releases/{}", api_url, project, release);
+            let value = download::get_json(&url, &[])?;
+            let release: GitHubRelease =
+                serde_json::from_value(value).map_err(|e| e.to_string())?;
+            let assets = release
+                .assets
+                .into_iter()
+                .map(|a| (a.name, a.browser_download_url))
+                .collect::<Vec<(String, String)>>();
+            (release.tag_name, assets)
+        }
+        "gitlab" => {
+            let release = match tag {
+                Some(tag) => tag.to_string(),
+                None => "permalink/latest".to_string(),
+            };
+            let project = project.replace('/', "%2F");
+            let url = format!("{}/projects/{}/releases/{}", api_url, project, release);
+            let value = download::get_json(&url, &[])?;
+            let release: GitLabRelease =
+                serde_json::from_value(value).map_err(|e| e.to_string())?;
+            let assets = release
+                .assets
+                .links
+                .into_iter()
+                .map(|l| (l.name, l.direct_asset_url.unwrap_or(l.url)))
+                .collect::<Vec<(String, String)>>();
+            (release.tag_name, assets)
+        }
+        _ => return Err(format!("unknown forge {}", forge)),
+    };
+
+    let asset = assets
+        .into_iter()
+        .find(|(name, _)| matches_pattern(pattern, name));
+    return match asset {
+        Some((name, url)) => Ok(ReleaseAsset {
+            tag: tag_name,
+            name,
+            url,
+        }),
+        None => Err(format!(
+            "release {} has no asset matching {}",
+            tag_name, pattern
+        )),
+    };
+}
+
+// Whether a name matches a pattern with `*` wildcards
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let parts = pattern.split('*').collect::<Vec<&str>>();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+
+    let first = parts[0];
+    let last = parts[parts.len() - 1];
+    if !name.starts_with(first) || name.len() < first.len() + last.len() {
+        return false;
+    }
+
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    return name.ends_with(last);
+}
+
+// "https://host" part of a repository url
+fn repository_host(git_repo: &str) -> Option<String> {
+    let (scheme, rest) = git_repo.split_once("://")?;
+    let host = rest.split('/').next()?;
+    return Some(format!("{}://{}", scheme, host));
+}
+
+// "owner/name" part of a repository url, for https and scp-like urls
+fn repository_path(git_repo: &str) -> Option<String> {
+    let path = match git_repo.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => git_repo.split_once(':')?.1,
+    };
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    if !path.contains('/') {
+        return None;
+    }
+    return Some(path.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(matches_pattern("addon.zip", "addon.zip"));
+        assert!(!matches_pattern("addon.zip", "addon.zip.sha256"));
+        assert!(matches_pattern("*.zip", "addon-v1.2.zip"));
+        assert!(!matches_pattern("*.zip", "addon-v1.2.tar.gz"));
+        assert!(matches_pattern("addon-*", "addon-v1.2.zip"));
+        assert!(!matches_pattern("addon-*", "other-v1.2.zip"));
+        assert!(matches_pattern(
+            "addon-*-godot4*.zip",
+            "addon-v1.2-godot4-linux.zip"
+        ));
+        assert!(!matches_pattern(
+            "addon-*-godot4*.zip",
+            "addon-v1.2-godot3.zip"
+        ));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("*addon*", "my-addon.zip"));
+        // The start and the end can't share characters
+        assert!(!matches_pattern("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn repository_paths() {
+        assert_eq!(
+            repository_path("https://github.com/owner/addon"),
+            Some("owner/addon".to_string())
+        );
+        assert_eq!(
+            repository_path("https://github.com/owner/addon.git"),
+            Some("owner/addon".to_string())
+        );
+        assert_eq!(
+            repository_path("https://gitlab.com/group/sub/addon/"),
+            Some("group/sub/addon".to_string())
+        );
+        assert_eq!(
+            repository_path("git@github.com:owner/addon.git"),
+            Some("owner/addon".to_string())
+        );
+        assert_eq!(
+            repository_path("ssh://git@gitlab.com/group/addon.git"),
+            Some("group/addon".to_string())
+        );
+        assert_eq!(repository_path("https://github.com/owner"), None);
+        assert_eq!(repository_path("addon"), None);
+    }
+
+    #[test]
+    fn api_urls() {
+        assert_eq!(
+            guess_forge("git@gitlab.example.com:group/addon.git"),
+            "gitlab"
+        );
+        assert_eq!(guess_forge("https://codeberg.org/owner/addon"), "github");
+        assert_eq!(
+            default_api_url("gitlab", "https://gitlab.example.com/group/addon"),
+            "https://gitlab.example.com/api/v4"
+        );
+        assert_eq!(
+            default_api_url("gitlab", "git@gitlab.com:group/addon.git"),
+            "https://gitlab.com/api/v4"
+        );
+        assert_eq!(
+            default_api_url("github", "git@github.com:owner/addon.git"),
+            "https://api.github.com"
+        );
+    }
+}
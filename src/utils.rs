@@ -1,32 +1,43 @@
-use colored::Colorize;
-use std::fmt::Debug;
-use std::io::Write;
+use colored::{ColoredString, Colorize};
+use std::fmt::Display;
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static LOG_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+// Keep stdout for JSON and other output meant for other programs
+pub fn log_to_stderr() {
+    LOG_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+fn log(msg: ColoredString) {
+    if LOG_TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{}", msg);
+    } else {
+        println!("{}", msg);
+    }
+}
 
 pub fn log_warning(msg: &str) {
-    let msg = format!("⚠️ {}", msg).yellow();
-    println!("{}", msg);
+    log(format!("⚠️ {}", msg).yellow());
 }
 
 pub fn log_error(msg: &str) {
-    let msg = format!("❌ {}", msg).red();
-    println!("{}", msg);
+    log(format!("❌ {}", msg).red());
 }
 
 pub fn log_info(msg: &str) {
-    let msg = format!("ℹ️  {}", msg).bright_blue();
-    println!("{}", msg);
+    log(format!("ℹ️  {}", msg).bright_blue());
 }
 
 pub fn log_check(msg: &str) {
-    let msg = format!("✅ {}", msg).bright_green();
-    println!("{}", msg);
+    log(format!("✅ {}", msg).bright_green());
 }
 
-pub fn assert_result<E>(res: &Result<String, E>, error_msg: &str) where E: Debug {
-    if res.is_err() {
+pub fn assert_result<T, E>(res: &Result<T, E>, error_msg: &str) where E: Display {
+    if let Err(e) = res {
         log_error(error_msg);
-        log_error(&format!("{:?}", res));
+        log_error(&format!("{}", e));
         exit(1);
     }
 }
@@ -61,30 +72,3 @@ pub fn get_repo_name(repo: &str) -> String {
     // TODO: Return a Result (may be error)
     return name.to_string();
 }
-
-pub fn run_shell_command(command: &str, folder: &str, verbose: bool) -> Result<String, String> {
-    let output = std::process::Command::new("sh")
-        .current_dir(folder)
-        .stdin(std::process::Stdio::inherit())
-        .arg("-c")
-        .arg(command)
-        .output()
-        .expect("Error running command.");
-
-    if verbose {
-        std::io::stdout().write_all(&output.stdout).unwrap();
-        std::io::stderr().write_all(&output.stderr).unwrap();
-    }
-
-    match output.status.success() {
-        true => {
-            let stdout_str = String::from_utf8(output.stdout).unwrap();
-            return Ok(stdout_str);
-        }
-
-        false => {
-            let stderr_str = String::from_utf8(output.stderr).unwrap();
-            return Err(stderr_str);
-        }
-    }
-}
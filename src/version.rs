@@ -0,0 +1,98 @@
+use semver::{Version, VersionReq};
+
+// Version of a tag like "v2.1.0", "2.1" or "v3"
+pub fn parse_tag_version(tag: &str) -> Option<Version> {
+    let version = tag.trim_start_matches(['v', 'V']);
+    if let Ok(parsed) = Version::parse(version) {
+        return Some(parsed);
+    }
+
+    // Pad partial versions, so "2.1" reads as "2.1.0"
+    let parts = version.split('.').collect::<Vec<&str>>();
+    if parts.len() > 2 || parts.iter().any(|p| p.parse::<u64>().is_err()) {
+        return None;
+    }
+
+    let mut padded = parts.join(".");
+    for _ in parts.len()..3 {
+        padded.push_str(".0");
+    }
+    return Version::parse(&padded).ok();
+}
+
+pub fn tag_matches(requirement: &VersionReq, tag: &str) -> bool {
+    return parse_tag_version(tag).is_some_and(|v| requirement.matches(&v));
+}
+
+// Highest version tag matching a requirement (or any release, without one)
+pub fn highest_tag(tags: &[String], requirement: Option<&VersionReq>) -> Option<String> {
+    return tags
+        .iter()
+        .filter_map(|tag| parse_tag_version(tag).map(|v| (v, tag)))
+        .filter(|(v, _)| match requirement {
+            Some(requirement) => requirement.matches(v),
+            None => v.pre.is_empty(),
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, tag)| tag.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag_list(names: &[&str]) -> Vec<String> {
+        return names.iter().map(|name| name.to_string()).collect();
+    }
+
+    #[test]
+    fn parse_tags() {
+        assert_eq!(parse_tag_version("v2.1.0"), Some(Version::new(2, 1, 0)));
+        assert_eq!(parse_tag_version("v2.1"), Some(Version::new(2, 1, 0)));
+        assert_eq!(parse_tag_version("3"), Some(Version::new(3, 0, 0)));
+        assert_eq!(parse_tag_version("V1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(
+            parse_tag_version("v2.0.0-beta.1"),
+            Some(Version::parse("2.0.0-beta.1").unwrap())
+        );
+        assert_eq!(parse_tag_version("v2.1-beta"), None);
+        assert_eq!(parse_tag_version("1.2.3.4"), None);
+        assert_eq!(parse_tag_version("release"), None);
+        assert_eq!(parse_tag_version(""), None);
+    }
+
+    #[test]
+    fn highest_release() {
+        let tags = tag_list(&["v1.0", "v2.1", "3", "v10.0.0-rc.1", "latest"]);
+        assert_eq!(highest_tag(&tags, None), Some("3".to_string()));
+        assert_eq!(highest_tag(&tag_list(&["nightly"]), None), None);
+    }
+
+    #[test]
+    fn highest_matching_tag() {
+        let tags = tag_list(&["v1.0", "v2.1", "v2.1.5", "3", "v3.1.0-beta.1"]);
+        let requirement = VersionReq::parse("^2").unwrap();
+        assert_eq!(
+            highest_tag(&tags, Some(&requirement)),
+            Some("v2.1.5".to_string())
+        );
+
+        let requirement = VersionReq::parse(">=3.1.0-beta").unwrap();
+        assert_eq!(
+            highest_tag(&tags, Some(&requirement)),
+            Some("v3.1.0-beta.1".to_string())
+        );
+
+        let requirement = VersionReq::parse("^4").unwrap();
+        assert_eq!(highest_tag(&tags, Some(&requirement)), None);
+    }
+
+    #[test]
+    fn matching_tags() {
+        let requirement = VersionReq::parse("~2.1").unwrap();
+        assert!(tag_matches(&requirement, "v2.1"));
+        assert!(tag_matches(&requirement, "2.1.9"));
+        assert!(!tag_matches(&requirement, "v2.2"));
+        assert!(!tag_matches(&requirement, "stable"));
+    }
+}